@@ -0,0 +1,160 @@
+#![allow(unused)]
+
+use crate::bit_writer::{BitOrder, bit_mask};
+use crate::byte_order::{ByteOrder, LittleEndian};
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+
+/// Bit-granular reader layered on a StreamBufReader.
+///
+/// Bytes are read from the StreamBufReader only when more bits are needed.
+/// `align()` discards any bits remaining in the current byte, so the StreamBufReader is left at the next byte boundary.
+/// ```
+/// # use stream_buf::{BitOrder, BitReader, StreamBufReader};
+/// let buf = [0xe0, 0xfb, 0x3f, 0x42];
+/// let mut sbuf_reader = StreamBufReader::new(&buf);
+///
+/// let mut bit_reader = BitReader::new(&mut sbuf_reader, BitOrder::LsbFirst);
+/// assert_eq!(0x3e0, bit_reader.read_bits(11));
+/// assert_eq!(0x7ff, bit_reader.read_bits(11));
+/// bit_reader.align();
+///
+/// assert_eq!(0x42, sbuf_reader.read_u8());
+/// ```
+pub struct BitReader<'r, 'a, E: ByteOrder = LittleEndian> {
+    reader: &'r mut StreamBufReader<'a, E>,
+    bit_order: BitOrder,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl<'r, 'a, E: ByteOrder> BitReader<'r, 'a, E> {
+    pub fn new(reader: &'r mut StreamBufReader<'a, E>, bit_order: BitOrder) -> Self {
+        Self {
+            reader,
+            bit_order,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    /// Return the number of bits read from the StreamBufReader that have not yet been returned.
+    pub fn pending_bits(&self) -> u32 {
+        self.bit_count
+    }
+
+    /// Return the next `n` bits, or a ReadError if there are not enough bytes remaining.
+    /// `n` is clamped to 32. On error nothing is consumed.
+    pub fn try_read_bits(&mut self, n: u32) -> Result<u32, ReadError> {
+        let n = n.min(u32::BITS);
+        if self.bit_count < n {
+            let len = (n - self.bit_count).div_ceil(8) as usize;
+            for byte in self.reader.try_read_slice(len)? {
+                self.bits = match self.bit_order {
+                    BitOrder::LsbFirst => self.bits | (u64::from(*byte) << self.bit_count),
+                    BitOrder::MsbFirst => (self.bits << 8) | u64::from(*byte),
+                };
+                self.bit_count += 8;
+            }
+        }
+
+        self.bit_count -= n;
+        let value = match self.bit_order {
+            BitOrder::LsbFirst => {
+                let value = self.bits & bit_mask(n);
+                self.bits >>= n;
+                value
+            }
+            BitOrder::MsbFirst => {
+                let value = (self.bits >> self.bit_count) & bit_mask(n);
+                self.bits &= bit_mask(self.bit_count);
+                value
+            }
+        };
+        Ok(value as u32)
+    }
+
+    /// Return the next `n` bits. `n` is clamped to 32.
+    /// Returns 0 if there are not enough bytes remaining, use `try_read_bits` to distinguish this from a genuine zero.
+    /// ```
+    /// # use stream_buf::{BitOrder, BitReader, StreamBufReader};
+    /// let buf = [0x82, 0xc6];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// // DShot frame: 11-bit throttle, telemetry request bit and 4-bit checksum, most significant bit first
+    /// let mut bit_reader = BitReader::new(&mut sbuf_reader, BitOrder::MsbFirst);
+    /// assert_eq!(1046, bit_reader.read_bits(11));
+    /// assert!(!bit_reader.read_bit());
+    /// assert_eq!(0x6, bit_reader.read_bits(4));
+    /// assert!(bit_reader.try_read_bits(1).is_err());
+    /// ```
+    pub fn read_bits(&mut self, n: u32) -> u32 {
+        self.try_read_bits(n).unwrap_or_default()
+    }
+
+    /// Return the next bit, or false if there are no bytes remaining.
+    pub fn read_bit(&mut self) -> bool {
+        self.read_bits(1) != 0
+    }
+
+    /// Discard any bits remaining in the current byte, so the next read starts on a byte boundary.
+    pub fn align(&mut self) {
+        self.bits = 0;
+        self.bit_count = 0;
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_bits_lsb_first() {
+        let buf = [
+            0xac, 0x00, 0xdf, 0xc4, 0x01, 0xf0, 0xff, 0x00, 0x00, 0x90, 0x3e, 0xac, 0x00, 0xdf, 0xc4, 0x01, 0xf0, 0xff,
+            0x00, 0x00, 0x90, 0x3e,
+        ];
+        let mut sbuf = StreamBufReader::new(&buf);
+        let mut bit_reader = BitReader::new(&mut sbuf, BitOrder::default());
+        let mut channels = [0u32; 16];
+        for channel in &mut channels {
+            *channel = bit_reader.read_bits(11);
+        }
+        assert_eq!(0, bit_reader.pending_bits());
+        assert_eq!(
+            [
+                172, 992, 1811, 0, 2047, 1, 1024, 500, 172, 992, 1811, 0, 2047, 1, 1024, 500
+            ],
+            channels
+        );
+        assert!(sbuf.is_full());
+    }
+
+    #[test]
+    fn read_bits_msb_first() {
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x00, 0xea, 0x0a];
+        let mut sbuf = StreamBufReader::new(&buf);
+        let mut bit_reader = BitReader::new(&mut sbuf, BitOrder::MsbFirst);
+        assert!(bit_reader.read_bit());
+        assert_eq!(0xffff_fffe, bit_reader.read_bits(32));
+        bit_reader.align();
+        assert_eq!(0x7, bit_reader.read_bits(3));
+        assert!(!bit_reader.read_bit());
+        assert_eq!(0x5, bit_reader.read_bits(3));
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 6,
+                requested: 2,
+                available: 1
+            }),
+            bit_reader.try_read_bits(10)
+        );
+        assert_eq!(1, bit_reader.pending_bits());
+        assert_eq!(0x0a, bit_reader.read_bits(9));
+        assert_eq!(0, bit_reader.read_bits(1));
+        assert_eq!(7, sbuf.pos());
+    }
+}
@@ -0,0 +1,212 @@
+#![allow(unused)]
+
+use crate::byte_order::{ByteOrder, LittleEndian};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+/// Order in which bits are packed into each byte by `BitWriter` and unpacked by `BitReader`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    /// The first bit written goes into the least significant bit of a byte, as used by CRSF and SBUS channel data.
+    #[default]
+    LsbFirst,
+    /// The first bit written goes into the most significant bit of a byte, as used by DShot frames.
+    MsbFirst,
+}
+
+/// Mask with the low `n` bits set, for `n` up to 63.
+pub(crate) fn bit_mask(n: u32) -> u64 {
+    (1u64 << n) - 1
+}
+
+/// Bit-granular writer layered on a StreamBufWriter.
+///
+/// Whole bytes are written to the StreamBufWriter as soon as they are complete.
+/// Any remaining bits are padded with zeros to the next byte boundary by `flush()`, or when the BitWriter is dropped.
+/// ```
+/// # use stream_buf::{BitOrder, BitWriter, StreamBufWriter};
+/// let mut data = [0u8; 4];
+/// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+///
+/// let mut bit_writer = BitWriter::new(&mut sbuf_writer, BitOrder::LsbFirst);
+/// bit_writer.write_bits(0x3e0, 11);
+/// bit_writer.write_bits(0x7ff, 11);
+/// drop(bit_writer);
+///
+/// assert_eq!([0xe0, 0xfb, 0x3f], sbuf_writer.get_ref());
+/// ```
+pub struct BitWriter<'w, 'a, E: ByteOrder = LittleEndian> {
+    writer: &'w mut StreamBufWriter<'a, E>,
+    bit_order: BitOrder,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl<'w, 'a, E: ByteOrder> BitWriter<'w, 'a, E> {
+    pub fn new(writer: &'w mut StreamBufWriter<'a, E>, bit_order: BitOrder) -> Self {
+        Self {
+            writer,
+            bit_order,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    /// Return the number of bits written that are not yet part of a complete byte.
+    pub fn pending_bits(&self) -> u32 {
+        self.bit_count
+    }
+
+    /// Write the low `n` bits of `value`, or return a WriteError if the completed bytes do not fit.
+    /// `n` is clamped to 32. On error nothing is written and the overflow flag of the StreamBufWriter is set.
+    pub fn try_write_bits(&mut self, value: u32, n: u32) -> Result<(), WriteError> {
+        let n = n.min(u32::BITS);
+        let value = u64::from(value) & bit_mask(n);
+        let bit_count = self.bit_count + n;
+        let bits = match self.bit_order {
+            BitOrder::LsbFirst => self.bits | (value << self.bit_count),
+            BitOrder::MsbFirst => (self.bits << n) | value,
+        };
+
+        let len = (bit_count / 8) as usize;
+        let mut bytes = [0u8; 5];
+        for (index, byte) in bytes[..len].iter_mut().enumerate() {
+            let shift = match self.bit_order {
+                BitOrder::LsbFirst => 8 * index as u32,
+                BitOrder::MsbFirst => bit_count - 8 * (index as u32 + 1),
+            };
+            *byte = (bits >> shift) as u8;
+        }
+        self.writer.try_write(&bytes[..len])?;
+
+        self.bit_count = bit_count % 8;
+        self.bits = match self.bit_order {
+            BitOrder::LsbFirst => bits >> (8 * len),
+            BitOrder::MsbFirst => bits & bit_mask(self.bit_count),
+        };
+        Ok(())
+    }
+
+    /// Write the low `n` bits of `value`. `n` is clamped to 32.
+    /// The bits are dropped and the overflow flag of the StreamBufWriter is set if the completed bytes do not fit.
+    /// ```
+    /// # use stream_buf::{BitOrder, BitWriter, StreamBufWriter};
+    /// let mut data = [0u8; 2];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// // DShot frame: 11-bit throttle, telemetry request bit and 4-bit checksum, most significant bit first
+    /// let mut bit_writer = BitWriter::new(&mut sbuf_writer, BitOrder::MsbFirst);
+    /// bit_writer.write_bits(1046, 11);
+    /// bit_writer.write_bit(false);
+    /// bit_writer.write_bits(0x6, 4);
+    /// drop(bit_writer);
+    ///
+    /// assert_eq!([0x82, 0xc6], sbuf_writer.get_ref());
+    /// ```
+    pub fn write_bits(&mut self, value: u32, n: u32) {
+        let _ = self.try_write_bits(value, n);
+    }
+
+    /// Write a single bit.
+    pub fn write_bit(&mut self, value: bool) {
+        self.write_bits(u32::from(value), 1);
+    }
+
+    /// Pad any pending bits with zeros to the next byte boundary and write the resulting byte,
+    /// or return a WriteError if there is no space available.
+    pub fn try_flush(&mut self) -> Result<(), WriteError> {
+        if self.bit_count == 0 {
+            return Ok(());
+        }
+        let byte = match self.bit_order {
+            BitOrder::LsbFirst => self.bits as u8,
+            BitOrder::MsbFirst => (self.bits << (8 - self.bit_count)) as u8,
+        };
+        self.writer.try_write_u8(byte)?;
+        self.bits = 0;
+        self.bit_count = 0;
+        Ok(())
+    }
+
+    /// Pad any pending bits with zeros to the next byte boundary and write the resulting byte.
+    pub fn flush(&mut self) {
+        let _ = self.try_flush();
+    }
+}
+
+impl<'w, 'a, E: ByteOrder> Drop for BitWriter<'w, 'a, E> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_bits_lsb_first() {
+        let channels = [172, 992, 1811, 0, 2047, 1, 1024, 500];
+        let mut data = [0u8; 24];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        {
+            let mut bit_writer = BitWriter::new(&mut sbuf, BitOrder::default());
+            for channel in channels.iter().chain(channels.iter()) {
+                bit_writer.write_bits(*channel, 11);
+            }
+            assert_eq!(0, bit_writer.pending_bits());
+        }
+        assert_eq!(
+            [
+                0xac, 0x00, 0xdf, 0xc4, 0x01, 0xf0, 0xff, 0x00, 0x00, 0x90, 0x3e, 0xac, 0x00, 0xdf, 0xc4, 0x01, 0xf0,
+                0xff, 0x00, 0x00, 0x90, 0x3e
+            ],
+            sbuf.get_ref()
+        );
+    }
+
+    #[test]
+    fn write_bits_msb_first() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        {
+            let mut bit_writer = BitWriter::new(&mut sbuf, BitOrder::MsbFirst);
+            bit_writer.write_bit(true);
+            bit_writer.write_bits(0xffff_fffe, 32);
+            assert_eq!(1, bit_writer.pending_bits());
+            bit_writer.flush();
+            assert_eq!(0, bit_writer.pending_bits());
+            // only the low n bits of the value are written
+            bit_writer.write_bits(0xff, 3);
+            bit_writer.write_bit(false);
+            bit_writer.write_bits(0x5, 3);
+        }
+        assert_eq!([0xff, 0xff, 0xff, 0xff, 0x00, 0xea], sbuf.get_ref());
+    }
+
+    #[test]
+    fn write_bits_overflow() {
+        let mut data = [0u8; 2];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        {
+            let mut bit_writer = BitWriter::new(&mut sbuf, BitOrder::LsbFirst);
+            assert_eq!(Ok(()), bit_writer.try_write_bits(0x1ff, 9));
+            assert_eq!(
+                Err(WriteError {
+                    pos: 1,
+                    requested: 2,
+                    available: 1
+                }),
+                bit_writer.try_write_bits(0xffff, 16)
+            );
+            assert_eq!(1, bit_writer.pending_bits());
+            assert_eq!(Ok(()), bit_writer.try_write_bits(0x2a, 7));
+            assert_eq!(Ok(()), bit_writer.try_flush());
+        }
+        assert_eq!([0xff, 0x55], sbuf.get_ref());
+        assert!(sbuf.overflowed());
+    }
+}
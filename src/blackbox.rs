@@ -0,0 +1,442 @@
+#![allow(unused)]
+
+//! Field encodings of the Betaflight blackbox flight log format, so logs written with a StreamBufWriter can be read by
+//! the existing blackbox tooling.
+//!
+//! * unsigned and signed variable byte: LEB128 varints, signed values zigzag encoded
+//! * `TAG8_8SVB`: a header byte flagging which of up to 8 fields are non-zero, then the non-zero fields as signed
+//!   variable byte
+//! * `TAG2_3S32`: 3 signed fields, packed into 2, 4 or 6 bits each, or 8 to 32 bits each with a per-field size
+//! * `TAG8_4S16`: a selector byte giving the size of each of 4 signed 16-bit fields, which are then packed as zero,
+//!   4, 8 or 16 bits, most significant nibble first
+//! * `NEG_14BIT`: the negation of a 14-bit signed value, as unsigned variable byte
+//!
+//! The multi-field encodings are written atomically: if there is not enough space for the whole group nothing is
+//! written. Reads leave the position unchanged on error.
+//! ```
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//!
+//! sbuf_writer.write_tag2_3s32(&[1, -2, 0]);
+//! sbuf_writer.write_tag8_4s16(&[0, 5, -100, 1000]);
+//! assert_eq!([0x18, 0xe4, 0x59, 0xc0, 0x3e, 0x80], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!([1, -2, 0], sbuf_reader.read_tag2_3s32());
+//! assert_eq!([0, 5, -100, 1000], sbuf_reader.read_tag8_4s16());
+//! ```
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::{BitOrder, BitWriter};
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+/// Sign extend the low `bits` bits of `value`.
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = u32::BITS - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// Return the `TAG8_4S16` selector for `value`: 0 for zero, 1 for 4 bits, 2 for 8 bits and 3 for 16 bits.
+fn tag8_4s16_selector(value: i16) -> u8 {
+    match value {
+        0 => 0,
+        -8..=7 => 1,
+        -128..=127 => 2,
+        _ => 3,
+    }
+}
+
+/// Return the number of bits used by a `TAG8_4S16` field with the given selector.
+fn tag8_4s16_bits(selector: u8) -> u32 {
+    match selector {
+        0 => 0,
+        selector => 2 << selector,
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write `value` as unsigned variable byte. This is the same encoding as `write_varint_u32`.
+    pub fn try_write_unsigned_vb(&mut self, value: u32) -> Result<(), WriteError> {
+        self.try_write_varint_u32(value)
+    }
+
+    /// Write `value` as signed variable byte. This is the same encoding as `write_varint_i32`.
+    pub fn try_write_signed_vb(&mut self, value: i32) -> Result<(), WriteError> {
+        self.try_write_varint_i32(value)
+    }
+
+    /// Write up to 8 `values` as `TAG8_8SVB`, or return a WriteError if there is not enough space available.
+    /// Any values beyond the eighth are ignored. A single value is written as signed variable byte with no header.
+    pub fn try_write_tag8_8svb(&mut self, values: &[i32]) -> Result<(), WriteError> {
+        let values = &values[..values.len().min(8)];
+        match values {
+            [] => return Ok(()),
+            [value] => return self.try_write_signed_vb(*value),
+            _ => {}
+        }
+        let mut data = [0u8; 41];
+        let mut encoded = StreamBufWriter::new(&mut data);
+        let header = values
+            .iter()
+            .enumerate()
+            .fold(0u8, |header, (index, value)| header | (u8::from(*value != 0) << index));
+        encoded.try_write_u8(header)?;
+        for value in values.iter().filter(|value| **value != 0) {
+            encoded.try_write_signed_vb(*value)?;
+        }
+        self.try_write(encoded.get_ref())
+    }
+
+    /// Write 3 `values` as `TAG2_3S32`, or return a WriteError if there is not enough space available.
+    pub fn try_write_tag2_3s32(&mut self, values: &[i32; 3]) -> Result<(), WriteError> {
+        let selector = values.iter().fold(0u8, |selector, value| match value {
+            -2..=1 => selector,
+            -8..=7 => selector.max(1),
+            -32..=31 => selector.max(2),
+            _ => 3,
+        });
+        let mut data = [0u8; 13];
+        let mut encoded = StreamBufWriter::new(&mut data);
+        let [v0, v1, v2] = values.map(|value| value as u8);
+        match selector {
+            0 => encoded.try_write_u8(((v0 & 0x03) << 4) | ((v1 & 0x03) << 2) | (v2 & 0x03))?,
+            1 => encoded.try_write(&[0x40 | (v0 & 0x0f), (v1 << 4) | (v2 & 0x0f)])?,
+            2 => encoded.try_write(&[0x80 | (v0 & 0x3f), v1, v2])?,
+            _ => {
+                let lens = values.map(|value| match value {
+                    -0x80..=0x7f => 1,
+                    -0x8000..=0x7fff => 2,
+                    -0x80_0000..=0x7f_ffff => 3,
+                    _ => 4,
+                });
+                let sizes = lens.iter().rev().fold(0u8, |sizes, len| (sizes << 2) | (len - 1));
+                encoded.try_write_u8(0xc0 | sizes)?;
+                for (value, len) in values.iter().zip(lens) {
+                    encoded.try_write(&value.to_le_bytes()[..len as usize])?;
+                }
+            }
+        }
+        self.try_write(encoded.get_ref())
+    }
+
+    /// Write 4 `values` as `TAG8_4S16`, or return a WriteError if there is not enough space available.
+    pub fn try_write_tag8_4s16(&mut self, values: &[i16; 4]) -> Result<(), WriteError> {
+        let selectors = values.map(tag8_4s16_selector);
+        let selector = selectors
+            .iter()
+            .rev()
+            .fold(0u8, |selector, field| (selector << 2) | field);
+        let mut data = [0u8; 9];
+        let mut encoded = StreamBufWriter::new(&mut data);
+        encoded.try_write_u8(selector)?;
+        let mut bit_writer = BitWriter::new(&mut encoded, BitOrder::MsbFirst);
+        for (value, field) in values.iter().zip(selectors) {
+            bit_writer.try_write_bits(*value as u32, tag8_4s16_bits(field))?;
+        }
+        bit_writer.try_flush()?;
+        drop(bit_writer);
+        self.try_write(encoded.get_ref())
+    }
+
+    /// Write `value` as `NEG_14BIT`, or return a WriteError if there is not enough space available.
+    /// Only values in the range -8191 to 8192 can be read back unchanged.
+    pub fn try_write_neg_14bit(&mut self, value: i32) -> Result<(), WriteError> {
+        self.try_write_unsigned_vb((value.wrapping_neg() as u32) & 0x3fff)
+    }
+
+    /// Write `value` as unsigned variable byte. This is the same encoding as `write_varint_u32`.
+    pub fn write_unsigned_vb(&mut self, value: u32) {
+        let _ = self.try_write_unsigned_vb(value);
+    }
+
+    /// Write `value` as signed variable byte. This is the same encoding as `write_varint_i32`.
+    pub fn write_signed_vb(&mut self, value: i32) {
+        let _ = self.try_write_signed_vb(value);
+    }
+
+    /// Write up to 8 `values` as `TAG8_8SVB`.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 8];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.write_tag8_8svb(&[0, -1, 100, 0, 0, 0, 0, -200]);
+    /// assert_eq!([0x86, 0x01, 0xc8, 0x01, 0x8f, 0x03], sbuf_writer.get_ref());
+    /// ```
+    pub fn write_tag8_8svb(&mut self, values: &[i32]) {
+        let _ = self.try_write_tag8_8svb(values);
+    }
+
+    /// Write 3 `values` as `TAG2_3S32`.
+    pub fn write_tag2_3s32(&mut self, values: &[i32; 3]) {
+        let _ = self.try_write_tag2_3s32(values);
+    }
+
+    /// Write 4 `values` as `TAG8_4S16`.
+    pub fn write_tag8_4s16(&mut self, values: &[i16; 4]) {
+        let _ = self.try_write_tag8_4s16(values);
+    }
+
+    /// Write `value` as `NEG_14BIT`.
+    pub fn write_neg_14bit(&mut self, value: i32) {
+        let _ = self.try_write_neg_14bit(value);
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Return a u32 read as unsigned variable byte. This is the same encoding as `read_varint_u32`.
+    pub fn try_read_unsigned_vb(&mut self) -> Result<u32, ReadError> {
+        self.try_read_varint_u32()
+    }
+
+    /// Return an i32 read as signed variable byte. This is the same encoding as `read_varint_i32`.
+    pub fn try_read_signed_vb(&mut self) -> Result<i32, ReadError> {
+        self.try_read_varint_i32()
+    }
+
+    /// Read `values.len()` values, up to 8, encoded as `TAG8_8SVB`, or return a ReadError if the data is truncated.
+    /// Any values beyond the eighth are set to zero. Nothing is read if `values` is empty, since nothing is written
+    /// for an empty slice.
+    pub fn try_read_tag8_8svb(&mut self, values: &mut [i32]) -> Result<(), ReadError> {
+        let mut reader = self.clone();
+        let count = values.len().min(8);
+        if count == 0 {
+            return Ok(());
+        }
+        if count == 1 {
+            values[0] = reader.try_read_signed_vb()?;
+        } else {
+            let header = reader.try_read_u8()?;
+            for (index, value) in values.iter_mut().enumerate() {
+                *value = if index < count && header & (1 << index) != 0 {
+                    reader.try_read_signed_vb()?
+                } else {
+                    0
+                };
+            }
+        }
+        *self = reader;
+        Ok(())
+    }
+
+    /// Return 3 values read as `TAG2_3S32`, or a ReadError if the data is truncated.
+    pub fn try_read_tag2_3s32(&mut self) -> Result<[i32; 3], ReadError> {
+        let mut reader = self.clone();
+        let lead = reader.try_read_u8()?;
+        let values = match lead >> 6 {
+            0 => [4, 2, 0].map(|shift| sign_extend(u32::from(lead >> shift), 2)),
+            1 => {
+                let byte = reader.try_read_u8()?;
+                [lead, byte >> 4, byte].map(|nibble| sign_extend(u32::from(nibble), 4))
+            }
+            2 => {
+                let byte1 = reader.try_read_u8()?;
+                let byte2 = reader.try_read_u8()?;
+                [lead, byte1, byte2].map(|byte| sign_extend(u32::from(byte), 6))
+            }
+            _ => {
+                let mut values = [0i32; 3];
+                for (index, value) in values.iter_mut().enumerate() {
+                    let len = ((lead >> (2 * index)) & 0x03) as usize + 1;
+                    let mut bytes = [0u8; 4];
+                    reader.try_read(&mut bytes[..len])?;
+                    *value = sign_extend(u32::from_le_bytes(bytes), 8 * len as u32);
+                }
+                values
+            }
+        };
+        *self = reader;
+        Ok(values)
+    }
+
+    /// Return 4 values read as `TAG8_4S16`, or a ReadError if the data is truncated.
+    pub fn try_read_tag8_4s16(&mut self) -> Result<[i16; 4], ReadError> {
+        let mut reader = self.clone();
+        let selector = reader.try_read_u8()?;
+        let mut values = [0i16; 4];
+        let mut bit_reader = BitReader::new(&mut reader, BitOrder::MsbFirst);
+        for (index, value) in values.iter_mut().enumerate() {
+            let bits = tag8_4s16_bits((selector >> (2 * index)) & 0x03);
+            if bits == 0 {
+                continue;
+            }
+            *value = sign_extend(bit_reader.try_read_bits(bits)?, bits) as i16;
+        }
+        *self = reader;
+        Ok(values)
+    }
+
+    /// Return a value read as `NEG_14BIT`, or a ReadError if the data is truncated.
+    pub fn try_read_neg_14bit(&mut self) -> Result<i32, ReadError> {
+        self.try_read_unsigned_vb()
+            .map(|value| -sign_extend(value & 0x3fff, 14))
+    }
+
+    /// Return a u32 read as unsigned variable byte, or 0 if the data is invalid.
+    pub fn read_unsigned_vb(&mut self) -> u32 {
+        self.try_read_unsigned_vb().unwrap_or_default()
+    }
+
+    /// Return an i32 read as signed variable byte, or 0 if the data is invalid.
+    pub fn read_signed_vb(&mut self) -> i32 {
+        self.try_read_signed_vb().unwrap_or_default()
+    }
+
+    /// Read `values.len()` values, up to 8, encoded as `TAG8_8SVB`. The values are left unchanged if the data is
+    /// truncated.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    /// let buf = [0x86, 0x01, 0xc8, 0x01, 0x8f, 0x03];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// let mut values = [0i32; 8];
+    /// sbuf_reader.read_tag8_8svb(&mut values);
+    /// assert_eq!([0, -1, 100, 0, 0, 0, 0, -200], values);
+    /// ```
+    pub fn read_tag8_8svb(&mut self, values: &mut [i32]) {
+        let mut read = [0i32; 8];
+        let count = values.len().min(8);
+        if self.try_read_tag8_8svb(&mut read[..count]).is_ok() {
+            values[..count].copy_from_slice(&read[..count]);
+            values[count..].fill(0);
+        }
+    }
+
+    /// Return 3 values read as `TAG2_3S32`, or zeros if the data is truncated.
+    pub fn read_tag2_3s32(&mut self) -> [i32; 3] {
+        self.try_read_tag2_3s32().unwrap_or_default()
+    }
+
+    /// Return 4 values read as `TAG8_4S16`, or zeros if the data is truncated.
+    pub fn read_tag8_4s16(&mut self) -> [i16; 4] {
+        self.try_read_tag8_4s16().unwrap_or_default()
+    }
+
+    /// Return a value read as `NEG_14BIT`, or 0 if the data is invalid.
+    pub fn read_neg_14bit(&mut self) -> i32 {
+        self.try_read_neg_14bit().unwrap_or_default()
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_byte() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_signed_vb(-1);
+        sbuf.write_signed_vb(300);
+        sbuf.write_unsigned_vb(300);
+        sbuf.write_neg_14bit(-3000);
+        sbuf.write_neg_14bit(42);
+        assert_eq!([0x01, 0xd8, 0x04, 0xac, 0x02, 0xb8, 0x17, 0xd6, 0x7f], sbuf.get_ref());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(-1, sbuf_reader.read_signed_vb());
+        assert_eq!(300, sbuf_reader.read_signed_vb());
+        assert_eq!(300, sbuf_reader.read_unsigned_vb());
+        assert_eq!(-3000, sbuf_reader.read_neg_14bit());
+        assert_eq!(42, sbuf_reader.read_neg_14bit());
+    }
+
+    #[test]
+    fn tag8_8svb() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_tag8_8svb(&[0, -1, 100, 0, 0, 0, 0, -200]);
+        sbuf.write_tag8_8svb(&[-2]);
+        sbuf.write_tag8_8svb(&[0, 0, 0]);
+        sbuf.write_tag8_8svb(&[]);
+        assert_eq!([0x86, 0x01, 0xc8, 0x01, 0x8f, 0x03, 0x03, 0x00], sbuf.get_ref());
+        assert_eq!(
+            Err(WriteError {
+                pos: 8,
+                requested: 12,
+                available: 8
+            }),
+            sbuf.try_write_tag8_8svb(&[i32::MIN, 1, 0, i32::MAX])
+        );
+        assert_eq!(8, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        let mut values = [7i32; 8];
+        sbuf_reader.read_tag8_8svb(&mut values);
+        assert_eq!([0, -1, 100, 0, 0, 0, 0, -200], values);
+        sbuf_reader.read_tag8_8svb(&mut values[..1]);
+        assert_eq!(-2, values[0]);
+        assert_eq!(Ok(()), sbuf_reader.try_read_tag8_8svb(&mut []));
+        assert_eq!(7, sbuf_reader.pos());
+        sbuf_reader.read_tag8_8svb(&mut values[..3]);
+        assert_eq!([0, 0, 0, 0], values[..4]);
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 8,
+                requested: 1,
+                available: 0
+            }),
+            sbuf_reader.try_read_tag8_8svb(&mut values[..2])
+        );
+    }
+
+    #[test]
+    fn tag2_3s32() {
+        let cases: [(&[i32; 3], &[u8]); 6] = [
+            (&[1, -2, 0], &[0x18]),
+            (&[7, -8, 2], &[0x47, 0x82]),
+            (&[31, -20, -32], &[0x9f, 0xec, 0xe0]),
+            (&[100, -1000, 0x123456], &[0xe4, 0x64, 0x18, 0xfc, 0x56, 0x34, 0x12]),
+            (&[-0x1234567, 0, -128], &[0xc3, 0x99, 0xba, 0xdc, 0xfe, 0x00, 0x80]),
+            (
+                &[i32::MIN, i32::MAX, 0],
+                &[0xcf, 0x00, 0x00, 0x00, 0x80, 0xff, 0xff, 0xff, 0x7f, 0x00],
+            ),
+        ];
+        for (values, bytes) in cases {
+            let mut data = [0u8; 16];
+            let mut sbuf = StreamBufWriter::new(&mut data);
+            assert_eq!(Ok(()), sbuf.try_write_tag2_3s32(values));
+            assert_eq!(bytes, sbuf.get_ref());
+
+            let mut sbuf_reader: StreamBufReader = sbuf.into();
+            assert_eq!(Ok(*values), sbuf_reader.try_read_tag2_3s32());
+            assert_eq!(bytes.len(), sbuf_reader.pos());
+        }
+
+        let mut sbuf_reader = StreamBufReader::new(&[0xe4, 0x64, 0x18, 0xfc, 0x56, 0x34]);
+        assert!(sbuf_reader.try_read_tag2_3s32().is_err());
+        assert_eq!(0, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn tag8_4s16() {
+        let cases: [(&[i16; 4], &[u8]); 4] = [
+            (&[0, 5, -100, 1000], &[0xe4, 0x59, 0xc0, 0x3e, 0x80]),
+            (&[-3, 0x1234, -8, 7], &[0x5d, 0xd1, 0x23, 0x48, 0x70]),
+            (&[0, 0, 0, 0], &[0x00]),
+            (&[-32768, 1, -129, 127], &[0xb7, 0x80, 0x00, 0x1f, 0xf7, 0xf7, 0xf0]),
+        ];
+        for (values, bytes) in cases {
+            let mut data = [0u8; 16];
+            let mut sbuf = StreamBufWriter::new(&mut data);
+            assert_eq!(Ok(()), sbuf.try_write_tag8_4s16(values));
+            assert_eq!(bytes, sbuf.get_ref());
+
+            let mut sbuf_reader: StreamBufReader = sbuf.into();
+            assert_eq!(Ok(*values), sbuf_reader.try_read_tag8_4s16());
+            assert_eq!(bytes.len(), sbuf_reader.pos());
+        }
+
+        let mut data = [0u8; 4];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert!(sbuf.try_write_tag8_4s16(&[-3, 0x1234, -8, 7]).is_err());
+        assert_eq!(0, sbuf.bytes_written());
+        let mut sbuf_reader = StreamBufReader::new(&[0x5d, 0xd1, 0x23, 0x48]);
+        assert_eq!([0, 0, 0, 0], sbuf_reader.read_tag8_4s16());
+        assert_eq!(0, sbuf_reader.pos());
+    }
+}
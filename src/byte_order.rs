@@ -0,0 +1,112 @@
+#![allow(unused)]
+
+//! Byte order markers used to parameterise `StreamBufWriter` and `StreamBufReader`.
+//!
+//! The unsuffixed functions, such as `write_u16` and `read_u16`, use the byte order of the stream_buf.
+//! The `_little_endian` and `_big_endian` functions always use the named byte order.
+//! ```
+//! # use stream_buf::{BigEndian, ByteOrder, StreamBufReader, StreamBufWriter, WriteError};
+//! fn write_header<E: ByteOrder>(sbuf_writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+//!     sbuf_writer.try_write_u16(0x0a1b)?;
+//!     sbuf_writer.try_write_u32(0x2c3d4e5f)
+//! }
+//!
+//! let mut data = [0u8; 6];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! write_header(&mut sbuf_writer).unwrap();
+//! assert_eq!([0x1b, 0x0a, 0x5f, 0x4e, 0x3d, 0x2c], data);
+//!
+//! let mut data = [0u8; 6];
+//! let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+//! write_header(&mut sbuf_writer).unwrap();
+//! assert_eq!([0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f], data);
+//! ```
+
+/// Byte order used by the unsuffixed read and write functions of a stream_buf.
+pub trait ByteOrder: Copy {
+    /// Return true if multi-byte values are stored most significant byte first.
+    fn is_big_endian(&self) -> bool;
+}
+
+/// Least significant byte first. This is the default byte order of a stream_buf.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LittleEndian;
+
+/// Most significant byte first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BigEndian;
+
+/// Network byte order, which is big endian.
+pub type NetworkEndian = BigEndian;
+
+/// Byte order selected at run time, for formats whose byte order is announced in a header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DynamicEndian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl ByteOrder for LittleEndian {
+    #[inline]
+    fn is_big_endian(&self) -> bool {
+        false
+    }
+}
+
+impl ByteOrder for BigEndian {
+    #[inline]
+    fn is_big_endian(&self) -> bool {
+        true
+    }
+}
+
+impl ByteOrder for DynamicEndian {
+    #[inline]
+    fn is_big_endian(&self) -> bool {
+        *self == DynamicEndian::Big
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+    use crate::{StreamBufReader, StreamBufWriter};
+
+    #[test]
+    fn dynamic_endian() {
+        let mut data = [0u8; 8];
+        let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, DynamicEndian::Big);
+        sbuf_writer.write_u16(0x0a1b);
+        sbuf_writer.set_byte_order(DynamicEndian::Little);
+        sbuf_writer.write_u16(0x0a1b);
+        sbuf_writer.write_i32_big_endian(-2);
+        assert_eq!([0x0a, 0x1b, 0x1b, 0x0a, 0xff, 0xff, 0xff, 0xfe], data);
+
+        // the first byte announces the byte order of the rest of the data
+        let buf = [0x01, 0x0a, 0x1b, 0x2c, 0x3d, 0x00, 0x00, 0xc0, 0x3f];
+        let mut sbuf_reader = StreamBufReader::with_byte_order(&buf, DynamicEndian::default());
+        if sbuf_reader.read_u8() == 0x01 {
+            sbuf_reader.set_byte_order(DynamicEndian::Big);
+        }
+        assert_eq!(DynamicEndian::Big, sbuf_reader.byte_order());
+        assert_eq!(0x0a1b2c3d, sbuf_reader.read_u32());
+        assert_eq!(1.5, sbuf_reader.read_f32_little_endian());
+    }
+
+    #[test]
+    fn network_endian() {
+        let mut data = [0u8; 16];
+        let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, NetworkEndian::default());
+        sbuf_writer.write_u16(0x0a1b);
+        sbuf_writer.write_f64(-1234.56);
+        sbuf_writer.write_u32_little_endian(0x2c3d4e5f);
+        assert_eq!([0x0a, 0x1b], sbuf_writer.get_ref()[0..2]);
+        assert_eq!([0x5f, 0x4e, 0x3d, 0x2c], sbuf_writer.get_ref()[10..14]);
+
+        let mut sbuf_reader: StreamBufReader<'_, NetworkEndian> = sbuf_writer.into();
+        assert_eq!(0x0a1b, sbuf_reader.read_u16());
+        assert_eq!(Ok(-1234.56), sbuf_reader.get::<f64>());
+        assert_eq!(0x2c3d4e5f, sbuf_reader.read_u32_little_endian());
+    }
+}
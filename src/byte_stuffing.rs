@@ -0,0 +1,626 @@
+#![allow(unused)]
+
+//! SLIP and HDLC-like byte-stuffed framing.
+//!
+//! * SLIP, RFC 1055: each frame is ended by END (0xC0). Within the frame END is sent as ESC (0xDB) followed by
+//!   ESC_END (0xDC), and ESC is sent as ESC followed by ESC_ESC (0xDD).
+//! * HDLC-like framing, as used by PPP in RFC 1662: each frame is ended by a flag (0x7E) and followed by a CRC-16/X-25
+//!   frame check sequence, least significant byte first. Within the frame, including the frame check sequence, the
+//!   flag and the escape byte (0x7D) are sent as the escape byte followed by the byte XORed with 0x20.
+//!
+//! The encoders also write the delimiter before each frame, which flushes any noise received since the last frame.
+//! The decoders skip empty frames, so consecutive delimiters are harmless.
+//! ```
+//! # use stream_buf::{SlipDecoder, StreamBufWriter};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.try_write_slip(&[0x01, 0xc0, 0xdb, 0x02]).unwrap();
+//! assert_eq!([0xc0, 0x01, 0xdb, 0xdc, 0xdb, 0xdd, 0x02, 0xc0], sbuf_writer.get_ref());
+//!
+//! let mut frame = [0u8; 16];
+//! let mut decoder = SlipDecoder::new(&mut frame);
+//! let mut frames = 0;
+//! for byte in sbuf_writer.get_ref() {
+//!     if let Ok(Some(sbuf_reader)) = decoder.decode(*byte) {
+//!         assert_eq!([0x01, 0xc0, 0xdb, 0x02], sbuf_reader.remaining_slice());
+//!         frames += 1;
+//!     }
+//! }
+//! assert_eq!(1, frames);
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::crc::{CRC_16_X25, Crc};
+use crate::stream_buf_reader::StreamBufReader;
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::fmt;
+
+const SLIP_END: u8 = 0xc0;
+const SLIP_ESC: u8 = 0xdb;
+const SLIP_ESC_END: u8 = 0xdc;
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+const HDLC_FLAG: u8 = 0x7e;
+const HDLC_ESCAPE: u8 = 0x7d;
+/// Value XORed with an escaped byte.
+const HDLC_ESCAPE_XOR: u8 = 0x20;
+/// Size of the frame check sequence.
+const HDLC_FCS_SIZE: usize = 2;
+
+/// Parameters of a byte stuffing scheme.
+struct Stuffing {
+    delimiter: u8,
+    escape: u8,
+    /// Return the byte sent after the escape byte in place of `byte`, or None if `byte` is sent as is.
+    escaped: fn(u8) -> Option<u8>,
+    /// CRC appended to the frame, least significant byte first, before stuffing.
+    crc: Option<&'static Crc>,
+}
+
+static SLIP_STUFFING: Stuffing = Stuffing {
+    delimiter: SLIP_END,
+    escape: SLIP_ESC,
+    escaped: |byte| match byte {
+        SLIP_END => Some(SLIP_ESC_END),
+        SLIP_ESC => Some(SLIP_ESC_ESC),
+        _ => None,
+    },
+    crc: None,
+};
+
+static HDLC_STUFFING: Stuffing = Stuffing {
+    delimiter: HDLC_FLAG,
+    escape: HDLC_ESCAPE,
+    escaped: |byte| match byte {
+        HDLC_FLAG | HDLC_ESCAPE => Some(byte ^ HDLC_ESCAPE_XOR),
+        _ => None,
+    },
+    crc: Some(&CRC_16_X25),
+};
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write `frame` as a SLIP frame, or return a WriteError if there is not enough space available.
+    /// On error nothing is written.
+    ///
+    /// The encoded frame takes at most `2 * frame.len() + 2` bytes.
+    pub fn try_write_slip(&mut self, frame: &[u8]) -> Result<(), WriteError> {
+        self.try_write_slip_with(|w| w.try_write(frame))
+    }
+
+    /// Write a SLIP frame whose contents are written by `f`.
+    ///
+    /// The contents are written directly to this StreamBufWriter and escaped in place. If `f` returns an error, a
+    /// write within `f` is dropped, or there is no space for the escape bytes, then the partial frame is rolled back
+    /// and an error is returned.
+    pub fn try_write_slip_with<F>(&mut self, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        self.try_write_stuffed_with(&SLIP_STUFFING, f)
+    }
+
+    /// Write `frame` as an HDLC-like frame with a CRC-16/X-25 frame check sequence, or return a WriteError if there
+    /// is not enough space available. On error nothing is written.
+    ///
+    /// Only the flag and the escape byte are escaped. The encoded frame takes at most `2 * frame.len() + 6` bytes.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// // the frame check sequence is 0x7e20, so its high byte is escaped
+    /// sbuf_writer.try_write_hdlc(&[0x2a]).unwrap();
+    /// assert_eq!([0x7e, 0x2a, 0x20, 0x7d, 0x5e, 0x7e], sbuf_writer.get_ref());
+    /// ```
+    pub fn try_write_hdlc(&mut self, frame: &[u8]) -> Result<(), WriteError> {
+        self.try_write_hdlc_with(|w| w.try_write(frame))
+    }
+
+    /// Write an HDLC-like frame whose contents are written by `f`, then append the frame check sequence.
+    ///
+    /// The contents are written directly to this StreamBufWriter and escaped in place. If `f` returns an error, a
+    /// write within `f` is dropped, or there is no space for the frame check sequence or the escape bytes, then the
+    /// partial frame is rolled back and an error is returned.
+    pub fn try_write_hdlc_with<F>(&mut self, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        self.try_write_stuffed_with(&HDLC_STUFFING, f)
+    }
+
+    fn try_write_stuffed_with<F>(&mut self, stuffing: &Stuffing, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let result = self.write_stuffed_frame(stuffing, f);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn write_stuffed_frame<F>(&mut self, stuffing: &Stuffing, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        self.try_write_u8(stuffing.delimiter)?;
+        let start = self.pos();
+        f(self)?;
+        self.check_no_drops_since(&checkpoint)?;
+        if let Some(crc) = stuffing.crc {
+            let fcs = self.crc_since(crc, start);
+            self.try_write(&fcs.to_le_bytes()[..crc.byte_len()])?;
+        }
+
+        // make space for the escape bytes, then move the frame up into it, escaping from the end
+        let end = self.pos();
+        let escapes = (start..end)
+            .filter(|index| (stuffing.escaped)(self[*index]).is_some())
+            .count();
+        self.try_fill(0, escapes)?;
+        let mut dest = self.pos();
+        for index in (start..end).rev() {
+            let byte = self[index];
+            match (stuffing.escaped)(byte) {
+                Some(escaped) => {
+                    dest -= 2;
+                    self[dest] = stuffing.escape;
+                    self[dest + 1] = escaped;
+                }
+                None => {
+                    dest -= 1;
+                    self[dest] = byte;
+                }
+            }
+        }
+        self.try_write_u8(stuffing.delimiter)
+    }
+}
+
+/// Error returned by `SlipDecoder::decode()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlipError {
+    /// The frame was aborted by an escape byte followed by END.
+    Aborted,
+    /// An escape byte was followed by a byte other than ESC_END or ESC_ESC. The rest of the frame is discarded.
+    BadEscape {
+        /// Byte following the escape byte.
+        byte: u8,
+    },
+    /// The frame is larger than the decoder's buffer. The rest of the frame is discarded.
+    Oversize {
+        /// Size of the decoder's buffer.
+        capacity: usize,
+    },
+}
+
+impl fmt::Display for SlipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Aborted => write!(f, "frame aborted"),
+            Self::BadEscape { byte } => write!(f, "invalid byte {byte:#04x} after escape"),
+            Self::Oversize { capacity } => write!(f, "frame larger than buffer of {capacity} bytes"),
+        }
+    }
+}
+
+impl core::error::Error for SlipError {}
+
+/// Error returned by `HdlcDecoder::decode()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HdlcError {
+    /// The frame was aborted by an escape byte followed by the flag.
+    Aborted,
+    /// An escape byte was followed by another escape byte. The rest of the frame is discarded.
+    BadEscape,
+    /// The frame is larger than the decoder's buffer. The rest of the frame is discarded.
+    Oversize {
+        /// Size of the decoder's buffer.
+        capacity: usize,
+    },
+    /// The frame is too short to hold the frame check sequence.
+    TooShort {
+        /// Length of the frame.
+        len: usize,
+    },
+    /// The frame check sequence received does not match the CRC of the frame.
+    Crc {
+        /// CRC calculated from the frame.
+        expected: u16,
+        /// Frame check sequence received.
+        received: u16,
+    },
+}
+
+impl fmt::Display for HdlcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Aborted => write!(f, "frame aborted"),
+            Self::BadEscape => write!(f, "escape byte after escape"),
+            Self::Oversize { capacity } => write!(f, "frame larger than buffer of {capacity} bytes"),
+            Self::TooShort { len } => write!(f, "frame of {len} bytes too short for frame check sequence"),
+            Self::Crc { expected, received } => {
+                write!(
+                    f,
+                    "frame check sequence {received:#06x} received but {expected:#06x} expected"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for HdlcError {}
+
+/// Frame buffer and escape state of a decoder.
+struct StuffedFrame<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+    /// Set when the previous byte was the escape byte.
+    escaped: bool,
+    /// Set after an error, until the next delimiter.
+    discarding: bool,
+}
+
+impl<'b> StuffedFrame<'b> {
+    fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            escaped: false,
+            discarding: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.escaped = false;
+        self.discarding = false;
+    }
+
+    /// Append `byte` to the frame, or return the capacity of the buffer if it is full.
+    fn push(&mut self, byte: u8) -> Result<(), usize> {
+        let capacity = self.buf.len();
+        let slot = self.buf.get_mut(self.len).ok_or(capacity)?;
+        *slot = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Return the length of the frame ended by a delimiter and reset for the next frame.
+    /// Returns None if there is no frame, or Err if the delimiter followed an escape byte.
+    fn finish(&mut self) -> Result<Option<usize>, ()> {
+        let (len, escaped, discarding) = (self.len, self.escaped, self.discarding);
+        self.reset();
+        if discarding {
+            Ok(None)
+        } else if escaped {
+            Err(())
+        } else {
+            Ok(Some(len).filter(|len| *len > 0))
+        }
+    }
+}
+
+/// Decoder for SLIP frames, fed one byte at a time, for example from a UART receive interrupt.
+///
+/// The unescaped frame is written into a buffer supplied by the caller, which limits the size of frame that can be
+/// received. After an error the rest of the frame is discarded.
+/// ```
+/// # use stream_buf::{SlipDecoder, SlipError};
+/// let bytes = [0xc0, 0x01, 0xdb, 0xc0, 0x02, 0xdb, 0xdd, 0xc0];
+/// let mut frame = [0u8; 16];
+/// let mut decoder = SlipDecoder::new(&mut frame);
+///
+/// assert!(bytes[..3].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+/// assert_eq!(Some(SlipError::Aborted), decoder.decode(bytes[3]).err());
+/// assert!(bytes[4..7].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+///
+/// let sbuf_reader = decoder.decode(bytes[7]).unwrap().unwrap();
+/// assert_eq!([0x02, 0xdb], sbuf_reader.remaining_slice());
+/// ```
+pub struct SlipDecoder<'b> {
+    frame: StuffedFrame<'b>,
+}
+
+impl<'b> SlipDecoder<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            frame: StuffedFrame::new(buf),
+        }
+    }
+
+    /// Return the size of the largest frame that can be received.
+    pub fn capacity(&self) -> usize {
+        self.frame.buf.len()
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.frame.reset();
+    }
+
+    /// Process the next received byte.
+    ///
+    /// Returns a reader over the frame when END is received, or a SlipError if the frame cannot be received.
+    pub fn decode(&mut self, byte: u8) -> Result<Option<StreamBufReader<'_>>, SlipError> {
+        let frame = &mut self.frame;
+        if byte == SLIP_END {
+            let len = frame.finish().map_err(|()| SlipError::Aborted)?;
+            return Ok(len.map(|len| StreamBufReader::new(&frame.buf[..len])));
+        }
+        if frame.discarding {
+            return Ok(None);
+        }
+
+        let oversize = |capacity| SlipError::Oversize { capacity };
+        let result = if frame.escaped {
+            frame.escaped = false;
+            match byte {
+                SLIP_ESC_END => frame.push(SLIP_END).map_err(oversize),
+                SLIP_ESC_ESC => frame.push(SLIP_ESC).map_err(oversize),
+                _ => Err(SlipError::BadEscape { byte }),
+            }
+        } else if byte == SLIP_ESC {
+            frame.escaped = true;
+            Ok(())
+        } else {
+            frame.push(byte).map_err(oversize)
+        };
+        if result.is_err() {
+            frame.discarding = true;
+        }
+        result.map(|()| None)
+    }
+}
+
+/// Decoder for HDLC-like frames, fed one byte at a time, for example from a UART receive interrupt.
+///
+/// The unescaped frame, including its frame check sequence, is written into a buffer supplied by the caller, which
+/// limits the size of frame that can be received. Any byte other than the flag and the escape byte may follow the
+/// escape byte, so control characters escaped by the sender are accepted. After an error the rest of the frame is
+/// discarded.
+/// ```
+/// # use stream_buf::{HdlcDecoder, HdlcError};
+/// let bytes = [0x7e, 0x2a, 0x20, 0x7d, 0x5e, 0x7e, 0x2b, 0x20, 0x7d, 0x5e, 0x7e];
+/// let mut frame = [0u8; 16];
+/// let mut decoder = HdlcDecoder::new(&mut frame);
+///
+/// assert!(bytes[..5].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+/// let sbuf_reader = decoder.decode(bytes[5]).unwrap().unwrap();
+/// assert_eq!([0x2a], sbuf_reader.remaining_slice());
+///
+/// assert!(bytes[6..10].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+/// assert_eq!(Some(HdlcError::Crc { expected: 0x6fa9, received: 0x7e20 }), decoder.decode(bytes[10]).err());
+/// ```
+pub struct HdlcDecoder<'b> {
+    frame: StuffedFrame<'b>,
+}
+
+impl<'b> HdlcDecoder<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            frame: StuffedFrame::new(buf),
+        }
+    }
+
+    /// Return the size of the largest frame, including its frame check sequence, that can be received.
+    pub fn capacity(&self) -> usize {
+        self.frame.buf.len()
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.frame.reset();
+    }
+
+    /// Process the next received byte.
+    ///
+    /// Returns a reader over the frame, without its frame check sequence, when the closing flag is received, or an
+    /// HdlcError if the frame cannot be received or its frame check sequence is wrong.
+    pub fn decode(&mut self, byte: u8) -> Result<Option<StreamBufReader<'_>>, HdlcError> {
+        let frame = &mut self.frame;
+        if byte == HDLC_FLAG {
+            let Some(len) = frame.finish().map_err(|()| HdlcError::Aborted)? else {
+                return Ok(None);
+            };
+            let Some(len) = len.checked_sub(HDLC_FCS_SIZE) else {
+                return Err(HdlcError::TooShort { len });
+            };
+            let expected = CRC_16_X25.checksum(&frame.buf[..len]) as u16;
+            let received = u16::from_le_bytes([frame.buf[len], frame.buf[len + 1]]);
+            if received != expected {
+                return Err(HdlcError::Crc { expected, received });
+            }
+            return Ok(Some(StreamBufReader::new(&frame.buf[..len])));
+        }
+        if frame.discarding {
+            return Ok(None);
+        }
+
+        let result = if frame.escaped {
+            frame.escaped = false;
+            if byte == HDLC_ESCAPE {
+                Err(HdlcError::BadEscape)
+            } else {
+                frame
+                    .push(byte ^ HDLC_ESCAPE_XOR)
+                    .map_err(|capacity| HdlcError::Oversize { capacity })
+            }
+        } else if byte == HDLC_ESCAPE {
+            frame.escaped = true;
+            Ok(())
+        } else {
+            frame.push(byte).map_err(|capacity| HdlcError::Oversize { capacity })
+        };
+        if result.is_err() {
+            frame.discarding = true;
+        }
+        result.map(|()| None)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slip() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_slip_with(|w| w.try_write_u16(0xdbc0)));
+        assert_eq!(Ok(()), sbuf.try_write_slip(&[]));
+        assert_eq!([0xc0, 0xdb, 0xdc, 0xdb, 0xdd, 0xc0, 0xc0, 0xc0], sbuf.get_ref());
+
+        // no space for the escape bytes, so nothing is written
+        assert_eq!(
+            Err(WriteError {
+                pos: 15,
+                requested: 2,
+                available: 1
+            }),
+            sbuf.try_write_slip(&[0xc0, 0x01, 0x02, 0x03, 0x04, 0xdb])
+        );
+        assert_eq!(8, sbuf.bytes_written());
+        assert!(!sbuf.overflowed());
+
+        // a dropped write within the frame is detected even though the overflow flag was already set
+        sbuf.write(&[0; 9]);
+        assert_eq!(
+            Err(WriteError {
+                pos: 10,
+                requested: 40,
+                available: 6
+            }),
+            sbuf.try_write_slip_with(|w| {
+                w.write_u8(0x01);
+                w.write(&[0; 40]);
+                w.write_u8(0x02);
+                Ok(())
+            })
+        );
+        assert_eq!(8, sbuf.bytes_written());
+
+        let mut buf = [0u8; 2];
+        let mut decoder = SlipDecoder::new(&mut buf);
+        assert_eq!(2, decoder.capacity());
+        let mut frames = 0;
+        for byte in sbuf.get_ref() {
+            if let Some(mut sbuf_reader) = decoder.decode(*byte).ok().flatten() {
+                assert_eq!(0xdbc0, sbuf_reader.read_u16());
+                frames += 1;
+            }
+        }
+        assert_eq!(1, frames);
+    }
+
+    #[test]
+    fn slip_errors() {
+        let mut buf = [0u8; 2];
+        let mut decoder = SlipDecoder::new(&mut buf);
+        let bytes = [0x01, 0xdb, 0x02, 0x03, 0xc0, 0x01, 0x02, 0x03, 0x04, 0xc0];
+        let results = bytes.map(|byte| decoder.decode(byte).map(|frame| frame.map(|_| ())));
+        assert_eq!(
+            [
+                Ok(None),
+                Ok(None),
+                Err(SlipError::BadEscape { byte: 0x02 }),
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Err(SlipError::Oversize { capacity: 2 }),
+                Ok(None),
+                Ok(None)
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn hdlc() {
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        // PPP LCP frame header, with a trailing flag byte that must be escaped
+        assert_eq!(Ok(()), sbuf.try_write_hdlc(&[0xff, 0x03, 0xc0, 0x21, 0x7e]));
+        assert_eq!(
+            [0x7e, 0xff, 0x03, 0xc0, 0x21, 0x7d, 0x5e, 0x68, 0xb5, 0x7e],
+            sbuf.get_ref()
+        );
+        // a dropped write within the frame rolls back the whole frame
+        assert_eq!(
+            Err(WriteError {
+                pos: 11,
+                requested: 32,
+                available: 21
+            }),
+            sbuf.try_write_hdlc_with(|w| {
+                w.write(&[0; 32]);
+                Ok(())
+            })
+        );
+        assert_eq!(10, sbuf.bytes_written());
+        sbuf.write(&[0; 32]);
+        assert_eq!(
+            Err(WriteError {
+                pos: 12,
+                requested: 40,
+                available: 20
+            }),
+            sbuf.try_write_hdlc_with(|w| {
+                w.write_u8(0x01);
+                w.write(&[0; 40]);
+                w.write_u8(0x02);
+                Ok(())
+            })
+        );
+        assert_eq!(10, sbuf.bytes_written());
+
+        let mut buf = [0u8; 8];
+        let mut decoder = HdlcDecoder::new(&mut buf);
+        assert_eq!(8, decoder.capacity());
+        let (flag, frame) = sbuf.get_ref().split_last().unwrap_or((&0, &[]));
+        for byte in frame {
+            assert_eq!(Ok(None), decoder.decode(*byte).map(|frame| frame.map(|_| ())));
+        }
+        assert_eq!(
+            Ok(Some([0xff, 0x03, 0xc0, 0x21, 0x7e].as_slice())),
+            decoder
+                .decode(*flag)
+                .map(|frame| frame.map(|reader| reader.remaining_slice()))
+        );
+    }
+
+    #[test]
+    fn hdlc_errors() {
+        let mut buf = [0u8; 4];
+        let mut decoder = HdlcDecoder::new(&mut buf);
+        let bytes = [
+            0x7e, 0x01, 0x7d, 0x7e, 0x01, 0x7d, 0x7d, 0x02, 0x7e, 0x01, 0x7e, 0x01, 0x02, 0x03, 0x04, 0x05, 0x7e,
+        ];
+        let results = bytes.map(|byte| decoder.decode(byte).map(|frame| frame.map(|_| ())));
+        assert_eq!(
+            [
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Err(HdlcError::Aborted),
+                Ok(None),
+                Ok(None),
+                Err(HdlcError::BadEscape),
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Err(HdlcError::TooShort { len: 1 }),
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Err(HdlcError::Oversize { capacity: 4 }),
+                Ok(None)
+            ],
+            results
+        );
+    }
+}
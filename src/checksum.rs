@@ -0,0 +1,374 @@
+#![allow(unused)]
+
+//! Running checksums, updated as bytes pass through a StreamBufWriter or StreamBufReader.
+//!
+//! `Checksummed` wraps a writer or reader and gives access to all of its functions. Each byte written or read through
+//! it is added to the checksum once, so the checksum of a frame is available as soon as its payload has been written
+//! or read, without a second pass over the data. If the stream is moved back, for example by `rollback()`, the
+//! checksum is rebuilt from the bytes that remain.
+//! ```
+//! # use stream_buf::{Checksummed, StreamBufWriter, Xor8};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.write_str("$M<");
+//!
+//! // MSP v1 request, checksummed over the size, command and payload
+//! let mut checksummed = Checksummed::new(&mut sbuf_writer, Xor8::default());
+//! checksummed.write_u8(2);
+//! checksummed.write_u8(101);
+//! checksummed.write_u16(0x0a1b);
+//! let checksum = checksummed.checksum();
+//! checksummed.write_u8(checksum);
+//!
+//! assert_eq!([b'$', b'M', b'<', 0x02, 0x65, 0x1b, 0x0a, 0x76], sbuf_writer.get_ref());
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::crc::CrcDigest;
+use crate::stream_buf_reader::StreamBufReader;
+use crate::stream_buf_writer::StreamBufWriter;
+use core::ops::{Deref, DerefMut};
+
+/// Checksum accumulator.
+pub trait Checksum {
+    type Output;
+
+    /// Add `data` to the checksum.
+    fn update(&mut self, data: &[u8]);
+
+    /// Return the checksum of the data added so far.
+    fn value(&self) -> Self::Output;
+}
+
+/// XOR of all bytes, as used by MSP v1 and NMEA.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Xor8(u8);
+
+/// Sum of all bytes, modulo 256.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sum8(u8);
+
+/// 8-bit Fletcher checksum, modulo 256, as used by UBX.
+/// The value has the first checksum byte, CK_A, in the low byte and the second, CK_B, in the high byte,
+/// so `write_u16_little_endian` writes them in the order UBX expects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fletcher16 {
+    ck_a: u8,
+    ck_b: u8,
+}
+
+/// Adler-32 checksum, as used by zlib.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Checksum for Xor8 {
+    type Output = u8;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0 = data.iter().fold(self.0, |checksum, byte| checksum ^ byte);
+    }
+
+    fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Checksum for Sum8 {
+    type Output = u8;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0 = data.iter().fold(self.0, |checksum, byte| checksum.wrapping_add(*byte));
+    }
+
+    fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Checksum for Fletcher16 {
+    type Output = u16;
+
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.ck_a = self.ck_a.wrapping_add(*byte);
+            self.ck_b = self.ck_b.wrapping_add(self.ck_a);
+        }
+    }
+
+    fn value(&self) -> u16 {
+        u16::from_le_bytes([self.ck_a, self.ck_b])
+    }
+}
+
+impl Adler32 {
+    const MODULUS: u32 = 65521;
+    /// Number of bytes that can be summed before `b` may overflow and must be reduced.
+    const BLOCK_LEN: usize = 5552;
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self { a: 1, b: 0 }
+    }
+}
+
+impl Checksum for Adler32 {
+    type Output = u32;
+
+    fn update(&mut self, data: &[u8]) {
+        for block in data.chunks(Self::BLOCK_LEN) {
+            for byte in block {
+                self.a += u32::from(*byte);
+                self.b += self.a;
+            }
+            self.a %= Self::MODULUS;
+            self.b %= Self::MODULUS;
+        }
+    }
+
+    fn value(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl<'c> Checksum for CrcDigest<'c> {
+    type Output = u32;
+
+    fn update(&mut self, data: &[u8]) {
+        CrcDigest::update(self, data);
+    }
+
+    fn value(&self) -> u32 {
+        self.finalize()
+    }
+}
+
+/// Stream whose bytes can be checksummed by `Checksummed`.
+pub trait ChecksumSource {
+    /// Return the bytes that have passed through the stream: those written by a writer, or read by a reader.
+    fn processed_bytes(&self) -> &[u8];
+
+    /// Return a count that changes whenever bytes already processed may have been changed, for example by moving a
+    /// writer back and writing again. The bytes of a reader cannot change, so by default this is always zero.
+    fn rewinds(&self) -> usize {
+        0
+    }
+}
+
+impl<'a, E: ByteOrder> ChecksumSource for StreamBufWriter<'a, E> {
+    fn processed_bytes(&self) -> &[u8] {
+        self.get_ref()
+    }
+
+    fn rewinds(&self) -> usize {
+        StreamBufWriter::rewinds(self)
+    }
+}
+
+impl<'a, E: ByteOrder> ChecksumSource for StreamBufReader<'a, E> {
+    fn processed_bytes(&self) -> &[u8] {
+        self.get_data_slice()
+    }
+}
+
+/// Wrapper around a StreamBufWriter or StreamBufReader that keeps a running checksum of the bytes written or read
+/// through it, starting from the position when it was created.
+///
+/// The bytes that have passed through since the last update are added when `checksum()`, `restart()` or
+/// `into_checksum()` is called. If the stream has been moved back since then, for example by `rollback()` or
+/// `reset()`, the checksum is instead rebuilt from the start, so discarded bytes are removed and rewritten bytes are
+/// added. Bytes changed in place behind the current position, for example by `fill_placeholder()`, are not
+/// re-checksummed.
+/// ```
+/// # use stream_buf::{Checksummed, Fletcher16, StreamBufReader};
+/// // UBX-CFG-RATE message: sync, class, id, length, payload and checksum
+/// let buf = [0xb5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xc8, 0x00, 0x01, 0x00, 0x01, 0x00, 0xde, 0x6a];
+/// let mut sbuf_reader = StreamBufReader::new(&buf);
+/// assert_eq!(0x62b5, sbuf_reader.read_u16());
+///
+/// let mut checksummed = Checksummed::new(&mut sbuf_reader, Fletcher16::default());
+/// let class_id = checksummed.read_u16_big_endian();
+/// let len = checksummed.read_u16() as usize;
+/// let payload = checksummed.read_slice(len);
+/// let checksum = checksummed.checksum();
+///
+/// assert_eq!((0x0608, Some([0xc8, 0x00, 0x01, 0x00, 0x01, 0x00].as_slice())), (class_id, payload));
+/// assert_eq!(checksum, sbuf_reader.read_u16());
+/// ```
+pub struct Checksummed<'s, W, C> {
+    stream: &'s mut W,
+    /// Accumulator as it was at `start`, from which the checksum is rebuilt if the stream moves back.
+    initial: C,
+    checksum: C,
+    start: usize,
+    pos: usize,
+    rewinds: usize,
+}
+
+impl<'s, W: ChecksumSource, C: Checksum + Clone> Checksummed<'s, W, C> {
+    pub fn new(stream: &'s mut W, checksum: C) -> Self {
+        let pos = stream.processed_bytes().len();
+        let rewinds = stream.rewinds();
+        Self {
+            stream,
+            initial: checksum.clone(),
+            checksum,
+            start: pos,
+            pos,
+            rewinds,
+        }
+    }
+
+    /// Add the bytes that have passed through the stream since the last update to the checksum, or rebuild the
+    /// checksum if the stream has moved back.
+    fn update(&mut self) {
+        let rewinds = self.stream.rewinds();
+        let bytes = self.stream.processed_bytes();
+        if bytes.len() < self.pos || rewinds != self.rewinds {
+            self.checksum = self.initial.clone();
+            self.pos = self.start;
+            self.rewinds = rewinds;
+        }
+        if let Some(data) = bytes.get(self.pos..) {
+            self.checksum.update(data);
+            self.pos = bytes.len();
+        }
+    }
+
+    /// Return the checksum of the bytes written or read so far.
+    pub fn checksum(&mut self) -> C::Output {
+        self.update();
+        self.checksum.value()
+    }
+
+    /// Start a new checksum from the current position, returning the accumulator of the previous one.
+    pub fn restart(&mut self, checksum: C) -> C {
+        self.update();
+        self.start = self.pos;
+        self.initial = checksum.clone();
+        core::mem::replace(&mut self.checksum, checksum)
+    }
+
+    /// Return the accumulator, up to date with the bytes written or read so far.
+    pub fn into_checksum(mut self) -> C {
+        self.update();
+        self.checksum
+    }
+}
+
+impl<'s, W, C> Deref for Checksummed<'s, W, C> {
+    type Target = W;
+    fn deref(&self) -> &Self::Target {
+        self.stream
+    }
+}
+
+impl<'s, W, C> DerefMut for Checksummed<'s, W, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stream
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+    use crate::crc::{CRC_8_DVB_S2, CRC_32};
+
+    fn checksum<C: Checksum>(mut checksum: C, data: &[u8]) -> C::Output {
+        checksum.update(data);
+        checksum.value()
+    }
+
+    #[test]
+    fn accumulators() {
+        assert_eq!(
+            0x27,
+            checksum(Xor8::default(), b"GPGLL,5057.970,N,00146.110,E,142451,A")
+        );
+        assert_eq!(0x97, checksum(Sum8::default(), b"Wikipedia"));
+        assert_eq!(
+            0x6ade,
+            checksum(
+                Fletcher16::default(),
+                &[0x06, 0x08, 0x06, 0x00, 0xc8, 0x00, 0x01, 0x00, 0x01, 0x00]
+            )
+        );
+        assert_eq!(0x11e6_0398, checksum(Adler32::default(), b"Wikipedia"));
+        assert_eq!(0x0000_0001, checksum(Adler32::default(), &[]));
+        assert_eq!(0xcbf4_3926, checksum(CRC_32.digest(), b"123456789"));
+
+        // the modulus is applied before the sums can overflow
+        let mut adler32 = Adler32::default();
+        for _ in 0..8 {
+            adler32.update(&[0xff; 5000]);
+        }
+        assert_eq!(0x0ab0_acd6, adler32.value());
+    }
+
+    #[test]
+    fn checksummed_writer() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u8(0xc8);
+        let mut checksummed = Checksummed::new(&mut sbuf, CRC_8_DVB_S2.digest());
+        checksummed.write_u8(0x08);
+        checksummed.write(&[0x00, 0x9a, 0x00, 0x05]);
+        assert_eq!(
+            CRC_8_DVB_S2.checksum(&[0x08, 0x00, 0x9a, 0x00, 0x05]),
+            checksummed.checksum()
+        );
+        checksummed.write(&[0x00, 0x00, 0x64, 0x46]);
+        let crc = checksummed.checksum() as u8;
+        checksummed.write_u8(crc);
+        assert_eq!(0x92, crc);
+
+        let previous = checksummed.restart(CRC_8_DVB_S2.digest());
+        assert_eq!(0x00, previous.finalize());
+        checksummed.write_u16(0x1234);
+        assert_eq!(
+            CRC_8_DVB_S2.checksum(&[0x34, 0x12]),
+            checksummed.into_checksum().finalize()
+        );
+        assert_eq!(13, sbuf.bytes_written());
+    }
+
+    #[test]
+    fn checksummed_rollback() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u8(0xff);
+        let mut checksummed = Checksummed::new(&mut sbuf, Sum8::default());
+        checksummed.write(&[0x01, 0x02]);
+        let checkpoint = checksummed.checkpoint();
+        checksummed.write(&[0x03, 0x04]);
+        assert_eq!(0x0a, checksummed.checksum());
+
+        // rewriting the same number of bytes after a rollback replaces them in the checksum
+        checksummed.rollback(checkpoint);
+        checksummed.write(&[0x10, 0x20, 0x30]);
+        assert_eq!(0x63, checksummed.checksum());
+
+        // moving back before the start leaves nothing to checksum
+        checksummed.reset();
+        assert_eq!(0x00, checksummed.checksum());
+        checksummed.write(&[0x40, 0x50]);
+        assert_eq!(0x50, checksummed.checksum());
+    }
+
+    #[test]
+    fn checksummed_reader() {
+        let buf = *b"$GPGLL,5057.970,N,00146.110,E,142451,A*27";
+        let mut sbuf = StreamBufReader::new(&buf);
+        assert_eq!(b'$', sbuf.read_u8());
+        let mut checksummed = Checksummed::new(&mut sbuf, Xor8::default());
+        while checksummed.peek::<u8>() != Ok(b'*') {
+            checksummed.advance(1);
+        }
+        let checksum = checksummed.checksum();
+        assert_eq!(Ok("*27"), sbuf.read_str(3));
+        assert_eq!(0x27, checksum);
+    }
+}
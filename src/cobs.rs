@@ -0,0 +1,388 @@
+#![allow(unused)]
+
+//! Consistent Overhead Byte Stuffing (COBS) framing, and its reduced variant COBS/R.
+//!
+//! COBS removes all zero bytes from a frame, so that a zero byte can be used as the frame delimiter. The frame is
+//! split into blocks, each starting with a code byte giving the offset of the next code byte. A code of 1 to 254
+//! means its block ends with a zero, which is implied rather than sent, and a code of 255 means a block of 254
+//! non-zero bytes without an implied zero. The overhead is one byte per 254 bytes of frame, at least one byte, plus
+//! the delimiter.
+//!
+//! COBS/R often saves the overhead byte: if the last byte of the frame is not less than the code of the final block,
+//! it replaces that code and is not sent at the end.
+//! ```
+//! # use stream_buf::{CobsDecoder, CobsVariant, StreamBufWriter};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.try_write_cobs(&[0x11, 0x22, 0x00, 0x33], CobsVariant::Cobs).unwrap();
+//! sbuf_writer.try_write_cobs(&[0x11, 0x22, 0x00, 0x33], CobsVariant::CobsR).unwrap();
+//! assert_eq!([0x03, 0x11, 0x22, 0x02, 0x33, 0x00, 0x03, 0x11, 0x22, 0x33, 0x00], sbuf_writer.get_ref());
+//!
+//! let mut frame = [0u8; 16];
+//! let mut decoder = CobsDecoder::new(&mut frame, CobsVariant::Cobs);
+//! let mut frames = 0;
+//! for byte in &sbuf_writer.get_ref()[..6] {
+//!     if let Ok(Some(sbuf_reader)) = decoder.decode(*byte) {
+//!         assert_eq!([0x11, 0x22, 0x00, 0x33], sbuf_reader.remaining_slice());
+//!         frames += 1;
+//!     }
+//! }
+//! assert_eq!(1, frames);
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::StreamBufReader;
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::fmt;
+
+/// Byte that ends each frame.
+const COBS_DELIMITER: u8 = 0x00;
+/// Code of a block of 254 non-zero bytes without an implied zero.
+const COBS_FULL_BLOCK: u8 = 0xff;
+
+/// Variant of COBS used by `try_write_cobs()` and `CobsDecoder`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CobsVariant {
+    /// Standard COBS.
+    #[default]
+    Cobs,
+    /// COBS/R, where the last byte of the frame may replace the code of the final block.
+    CobsR,
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write `frame` COBS encoded and followed by the delimiter, or return a WriteError if there is not enough space
+    /// available. On error nothing is written.
+    ///
+    /// To encode the contents of another StreamBufWriter, pass its `get_ref()`.
+    /// The encoded frame takes at most `frame.len() + frame.len() / 254 + 2` bytes.
+    pub fn try_write_cobs(&mut self, frame: &[u8], variant: CobsVariant) -> Result<(), WriteError> {
+        self.try_write_cobs_with(variant, |w| w.try_write(frame))
+    }
+
+    /// Write a COBS encoded frame, whose contents are written by `f`, followed by the delimiter.
+    ///
+    /// The contents are written directly to this StreamBufWriter and encoded in place, so no intermediate buffer is
+    /// needed, but space is needed for the encoded frame. If `f` returns an error, a write within `f` is dropped, or
+    /// there is no space for the encoding overhead, then the partial frame is rolled back and an error is returned.
+    /// ```
+    /// # use stream_buf::{CobsVariant, StreamBufWriter};
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.try_write_cobs_with(CobsVariant::Cobs, |w| w.try_write_u32(0x0000_0102)).unwrap();
+    /// assert_eq!([0x03, 0x02, 0x01, 0x01, 0x01, 0x00], sbuf_writer.get_ref());
+    /// ```
+    pub fn try_write_cobs_with<F>(&mut self, variant: CobsVariant, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let result = self.write_cobs_frame(variant, f);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn write_cobs_frame<F>(&mut self, variant: CobsVariant, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        // placeholder for the first code
+        let mut code_pos = self.pos();
+        self.try_write_u8(0)?;
+        f(self)?;
+        self.check_no_drops_since(&checkpoint)?;
+
+        // replace each zero with the code of the block it ends, splitting blocks of more than 254 non-zero bytes
+        let mut index = code_pos + 1;
+        while index < self.pos() {
+            if self[index] == 0 {
+                self[code_pos] = (index - code_pos) as u8;
+                code_pos = index;
+            } else if index - code_pos == usize::from(COBS_FULL_BLOCK - 1) && index + 1 < self.pos() {
+                self[code_pos] = COBS_FULL_BLOCK;
+                code_pos = index + 1;
+                self.insert_cobs_code(code_pos)?;
+                index += 1;
+            }
+            index += 1;
+        }
+
+        let code = (self.pos() - code_pos) as u8;
+        let last = self.pos() - 1;
+        if variant == CobsVariant::CobsR && code > 1 && self[last] >= code {
+            // the last byte replaces the final code, and is itself replaced by the delimiter
+            self[code_pos] = self[last];
+            self[last] = COBS_DELIMITER;
+            return Ok(());
+        }
+        self[code_pos] = code;
+        self.try_write_u8(COBS_DELIMITER)
+    }
+
+    /// Move the bytes from `pos` up by one to make space for a code.
+    fn insert_cobs_code(&mut self, pos: usize) -> Result<(), WriteError> {
+        self.try_write_u8(0)?;
+        for index in (pos..self.pos() - 1).rev() {
+            self[index + 1] = self[index];
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by `CobsDecoder::decode()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CobsError {
+    /// The decoded frame is larger than the decoder's buffer. The rest of the frame is discarded.
+    Oversize {
+        /// Size of the decoder's buffer.
+        capacity: usize,
+    },
+    /// The delimiter was received part way through a block, so bytes of the frame have been lost.
+    Truncated,
+}
+
+impl fmt::Display for CobsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Oversize { capacity } => write!(f, "frame larger than buffer of {capacity} bytes"),
+            Self::Truncated => write!(f, "frame ended part way through a block"),
+        }
+    }
+}
+
+impl core::error::Error for CobsError {}
+
+/// Decoder for COBS and COBS/R frames, fed one byte at a time, for example from a UART receive interrupt.
+///
+/// The decoded frame is written into a buffer supplied by the caller, which limits the size of frame that can be
+/// received. Consecutive delimiters are skipped, so a sender can send a delimiter before each frame to flush any noise.
+/// ```
+/// # use stream_buf::{CobsDecoder, CobsError, CobsVariant};
+/// let bytes = [0x03, 0x11, 0x00, 0x00, 0x02, 0x11, 0x01, 0x00];
+/// let mut frame = [0u8; 16];
+/// let mut decoder = CobsDecoder::new(&mut frame, CobsVariant::Cobs);
+///
+/// assert!(bytes[..2].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+/// assert_eq!(Some(CobsError::Truncated), decoder.decode(bytes[2]).err());
+/// assert!(bytes[3..7].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+///
+/// let mut sbuf_reader = decoder.decode(bytes[7]).unwrap().unwrap();
+/// assert_eq!(0x0011, sbuf_reader.read_u16());
+/// ```
+pub struct CobsDecoder<'b> {
+    buf: &'b mut [u8],
+    variant: CobsVariant,
+    len: usize,
+    /// Code of the current block, or zero if no bytes of the frame have been received.
+    code: u8,
+    /// Number of bytes of the current block still to be received.
+    remaining: u8,
+    /// Set after an error, until the next delimiter.
+    discarding: bool,
+}
+
+impl<'b> CobsDecoder<'b> {
+    pub fn new(buf: &'b mut [u8], variant: CobsVariant) -> Self {
+        Self {
+            buf,
+            variant,
+            len: 0,
+            code: 0,
+            remaining: 0,
+            discarding: false,
+        }
+    }
+
+    /// Return the size of the largest frame that can be received.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.code = 0;
+        self.remaining = 0;
+        self.discarding = false;
+    }
+
+    /// Append `byte` to the decoded frame, or return an error if the buffer is full.
+    fn push(&mut self, byte: u8) -> Result<(), CobsError> {
+        let capacity = self.buf.len();
+        let slot = self.buf.get_mut(self.len).ok_or(CobsError::Oversize { capacity })?;
+        *slot = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Return the length of the frame ended by a delimiter, or None if there is no frame to return.
+    fn finish_frame(&mut self) -> Result<Option<usize>, CobsError> {
+        if self.discarding || self.code == 0 {
+            return Ok(None);
+        }
+        if self.remaining > 0 {
+            match self.variant {
+                CobsVariant::Cobs => return Err(CobsError::Truncated),
+                // the final code is also the last byte of the frame
+                CobsVariant::CobsR => self.push(self.code)?,
+            }
+        }
+        Ok(Some(self.len))
+    }
+
+    /// Process the next received byte.
+    ///
+    /// Returns a reader over the decoded frame when the delimiter is received, or a CobsError if the frame cannot be
+    /// decoded. After an error the rest of the frame is discarded.
+    pub fn decode(&mut self, byte: u8) -> Result<Option<StreamBufReader<'_>>, CobsError> {
+        if byte == COBS_DELIMITER {
+            let result = self.finish_frame();
+            self.reset();
+            return result.map(|len| len.map(|len| StreamBufReader::new(&self.buf[..len])));
+        }
+        if self.discarding {
+            return Ok(None);
+        }
+
+        let result = if self.remaining == 0 {
+            let implied_zero = self.code != 0 && self.code != COBS_FULL_BLOCK;
+            self.code = byte;
+            self.remaining = byte - 1;
+            if implied_zero { self.push(0) } else { Ok(()) }
+        } else {
+            self.remaining -= 1;
+            self.push(byte)
+        };
+        if result.is_err() {
+            self.discarding = true;
+        }
+        result.map(|()| None)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    /// Return the concatenation of `parts`, which must be `N` bytes long in total.
+    fn concat<const N: usize>(parts: &[&[u8]]) -> [u8; N] {
+        let mut result = [0u8; N];
+        let mut len = 0;
+        for part in parts {
+            result[len..len + part.len()].copy_from_slice(part);
+            len += part.len();
+        }
+        assert_eq!(N, len);
+        result
+    }
+
+    /// Encode `frame`, check the encoding is `expected`, then check it decodes back to `frame`.
+    fn round_trip(frame: &[u8], expected: &[u8], variant: CobsVariant) {
+        let mut data = [0u8; 520];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_cobs(frame, variant));
+        assert_eq!(expected, sbuf.get_ref());
+
+        let mut buf = [0u8; 512];
+        let mut decoder = CobsDecoder::new(&mut buf, variant);
+        let (delimiter, encoded) = expected.split_last().unwrap_or((&0, &[]));
+        for byte in encoded {
+            assert_eq!(Ok(None), decoder.decode(*byte).map(|frame| frame.map(|_| ())));
+        }
+        let decoded = decoder
+            .decode(*delimiter)
+            .map(|frame| frame.map(|reader| reader.remaining_slice()));
+        assert_eq!(Ok(Some(frame)), decoded);
+    }
+
+    #[test]
+    fn cobs() {
+        let cobs = CobsVariant::Cobs;
+        round_trip(&[], &[0x01, 0x00], cobs);
+        round_trip(&[0x00], &[0x01, 0x01, 0x00], cobs);
+        round_trip(&[0x00, 0x11, 0x00], &[0x01, 0x02, 0x11, 0x01, 0x00], cobs);
+        round_trip(&[0x11, 0x22, 0x33, 0x44], &[0x05, 0x11, 0x22, 0x33, 0x44, 0x00], cobs);
+        round_trip(&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01, 0x00], cobs);
+
+        let mut frame = [0u8; 256];
+        for (index, byte) in frame.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        // 0x01 to 0xfe is a full block, ending the frame
+        round_trip(
+            &frame[1..255],
+            &concat::<256>(&[&[0xff], &frame[1..255], &[0x00]]),
+            cobs,
+        );
+        // 0x00 to 0xfe
+        round_trip(
+            &frame[..255],
+            &concat::<257>(&[&[0x01, 0xff], &frame[1..255], &[0x00]]),
+            cobs,
+        );
+        // 0x01 to 0xff, so the full block is followed by another
+        round_trip(
+            &frame[1..],
+            &concat::<258>(&[&[0xff], &frame[1..255], &[0x02, 0xff, 0x00]]),
+            cobs,
+        );
+    }
+
+    #[test]
+    fn cobs_r() {
+        let cobs_r = CobsVariant::CobsR;
+        round_trip(&[], &[0x01, 0x00], cobs_r);
+        round_trip(&[0x01], &[0x02, 0x01, 0x00], cobs_r);
+        round_trip(&[0x02], &[0x02, 0x00], cobs_r);
+        round_trip(&[0x00, 0x11], &[0x01, 0x11, 0x00], cobs_r);
+        round_trip(&[0x11, 0x22, 0x33, 0x44], &[0x44, 0x11, 0x22, 0x33, 0x00], cobs_r);
+        round_trip(&[0x11, 0x22, 0x33, 0x04], &[0x05, 0x11, 0x22, 0x33, 0x04, 0x00], cobs_r);
+    }
+
+    #[test]
+    fn errors() {
+        // no space for the overhead, so nothing is written
+        let mut data = [0u8; 5];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Err(WriteError {
+                pos: 5,
+                requested: 1,
+                available: 0
+            }),
+            sbuf.try_write_cobs(&[0x11, 0x22, 0x33, 0x44], CobsVariant::Cobs)
+        );
+        assert_eq!(0, sbuf.bytes_written());
+        assert!(!sbuf.overflowed());
+
+        let mut buf = [0u8; 2];
+        let mut decoder = CobsDecoder::new(&mut buf, CobsVariant::Cobs);
+        assert_eq!(2, decoder.capacity());
+        let oversize = [0x04, 0x11, 0x22, 0x33, 0x00];
+        let errors = oversize.map(|byte| decoder.decode(byte).map(|frame| frame.map(|_| ())));
+        assert_eq!(
+            [
+                Ok(None),
+                Ok(None),
+                Ok(None),
+                Err(CobsError::Oversize { capacity: 2 }),
+                Ok(None)
+            ],
+            errors
+        );
+        // the decoder recovers at the next frame
+        for byte in [0x02, 0x11] {
+            assert_eq!(Ok(None), decoder.decode(byte).map(|frame| frame.map(|_| ())));
+        }
+        assert_eq!(
+            Ok(Some([0x11].as_slice())),
+            decoder
+                .decode(0x00)
+                .map(|frame| frame.map(|reader| reader.remaining_slice()))
+        );
+    }
+}
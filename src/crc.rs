@@ -0,0 +1,291 @@
+#![allow(unused)]
+
+//! Table-driven CRC engine for CRCs of 1 to 32 bits, described by their width, polynomial, initial value, reflection
+//! and final xor, with presets for the CRCs used by the framings in this crate.
+//!
+//! The table is computed by a `const fn`, so CRCs defined as statics cost no run-time initialisation.
+//! ```
+//! # use stream_buf::{CRC_8_DVB_S2, Crc, StreamBufReader, StreamBufWriter};
+//! static CRC_16_XMODEM: Crc = Crc::new(16, 0x1021, 0x0000, false, 0x0000);
+//! assert_eq!(0x31c3, CRC_16_XMODEM.checksum(b"123456789"));
+//!
+//! let mut data = [0u8; 8];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.write_u8(0xc8);
+//! sbuf_writer.write_u16(0x0a1b);
+//! let crc = sbuf_writer.crc_since(&CRC_8_DVB_S2, 1);
+//! sbuf_writer.write_u8(crc as u8);
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! sbuf_reader.advance(3);
+//! assert_eq!(Ok(()), sbuf_reader.verify_crc8(1..3));
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::StreamBufWriter;
+use core::ops::Range;
+
+/// CRC algorithm, with its lookup table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crc {
+    width: u32,
+    poly: u32,
+    init: u32,
+    reflect: bool,
+    xorout: u32,
+    table: [u32; 256],
+}
+
+/// CRC-8/DVB-S2, as used by CRSF and MSP v2. Check value 0xbc.
+pub static CRC_8_DVB_S2: Crc = Crc::new(8, 0xd5, 0x00, false, 0x00);
+/// CRC-16/CCITT, with the parameters also known as CRC-16/CCITT-FALSE or CRC-16/IBM-3740. Check value 0x29b1.
+pub static CRC_16_CCITT: Crc = Crc::new(16, 0x1021, 0xffff, false, 0x0000);
+/// CRC-16/X-25, the reflected CCITT CRC used as the frame check sequence of HDLC. Check value 0x906e.
+pub static CRC_16_X25: Crc = Crc::new(16, 0x1021, 0xffff, true, 0xffff);
+/// CRC-16/MODBUS. Check value 0x4b37.
+pub static CRC_16_MODBUS: Crc = Crc::new(16, 0x8005, 0xffff, true, 0x0000);
+/// CRC-32, as used by Ethernet, zlib and PNG. Check value 0xcbf43926.
+pub static CRC_32: Crc = Crc::new(32, 0x04c1_1db7, 0xffff_ffff, true, 0xffff_ffff);
+
+impl Crc {
+    /// Create a CRC of `width` bits, from 1 to 32, with polynomial `poly`, initial value `init` and final xor `xorout`.
+    /// If `reflect` is true the input bytes and the result are bit reversed, that is the CRC is computed least
+    /// significant bit first. The parameters are as given in the catalogue of parametrised CRC algorithms.
+    pub const fn new(width: u32, poly: u32, init: u32, reflect: bool, xorout: u32) -> Self {
+        let width = if width == 0 {
+            1
+        } else if width > 32 {
+            32
+        } else {
+            width
+        };
+        let shift = u32::BITS - width;
+        let mut table = [0u32; 256];
+        let mut index = 0;
+        while index < 256 {
+            let mut value = index as u32;
+            let mut bit = 0;
+            if reflect {
+                let poly = poly.reverse_bits() >> shift;
+                while bit < 8 {
+                    value = if value & 1 != 0 {
+                        (value >> 1) ^ poly
+                    } else {
+                        value >> 1
+                    };
+                    bit += 1;
+                }
+            } else {
+                let poly = poly << shift;
+                value <<= 24;
+                while bit < 8 {
+                    value = if value & 0x8000_0000 != 0 {
+                        (value << 1) ^ poly
+                    } else {
+                        value << 1
+                    };
+                    bit += 1;
+                }
+            }
+            table[index] = value;
+            index += 1;
+        }
+        let mask = u32::MAX >> shift;
+        Self {
+            width,
+            poly: poly & mask,
+            init: init & mask,
+            reflect,
+            xorout: xorout & mask,
+            table,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Return the number of bytes needed to hold a CRC value.
+    pub fn byte_len(&self) -> usize {
+        self.width.div_ceil(8) as usize
+    }
+
+    /// Return a digest for computing the CRC incrementally.
+    pub fn digest(&self) -> CrcDigest<'_> {
+        let shift = u32::BITS - self.width;
+        let register = if self.reflect {
+            self.init.reverse_bits() >> shift
+        } else {
+            self.init << shift
+        };
+        CrcDigest { crc: self, register }
+    }
+
+    /// Return the CRC of `data`.
+    pub fn checksum(&self, data: &[u8]) -> u32 {
+        let mut digest = self.digest();
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+/// Incremental CRC computation, returned by `Crc::digest()`.
+#[derive(Clone, Debug)]
+pub struct CrcDigest<'c> {
+    crc: &'c Crc,
+    register: u32,
+}
+
+impl<'c> CrcDigest<'c> {
+    /// Add `data` to the CRC.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.update_u8(*byte);
+        }
+    }
+
+    /// Add a single byte to the CRC.
+    pub fn update_u8(&mut self, byte: u8) {
+        self.register = if self.crc.reflect {
+            (self.register >> 8) ^ self.crc.table[((self.register ^ u32::from(byte)) & 0xff) as usize]
+        } else {
+            (self.register << 8) ^ self.crc.table[((self.register >> 24) ^ u32::from(byte)) as usize]
+        };
+    }
+
+    /// Return the CRC of the data added so far. More data may still be added.
+    pub fn finalize(&self) -> u32 {
+        let register = if self.crc.reflect {
+            self.register
+        } else {
+            self.register >> (u32::BITS - self.crc.width)
+        };
+        register ^ self.crc.xorout
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Return the CRC of the bytes written from position `pos` up to the current position.
+    /// Returns the CRC of no data if `pos` is beyond the current position.
+    pub fn crc_since(&self, crc: &Crc, pos: usize) -> u32 {
+        crc.checksum(self.get_ref().get(pos..).unwrap_or_default())
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Return the CRC of the bytes in `range` of the underlying buffer,
+    /// or a ReadError if the range is not within the buffer.
+    pub fn crc(&self, crc: &Crc, range: Range<usize>) -> Result<u32, ReadError> {
+        let buf = self.get_data();
+        match buf.get(range.clone()) {
+            Some(data) => Ok(crc.checksum(data)),
+            None => Err(ReadError::Truncated {
+                pos: range.start,
+                requested: range.end.saturating_sub(range.start),
+                available: buf.len().saturating_sub(range.start),
+            }),
+        }
+    }
+
+    /// Read a CRC-8/DVB-S2 byte and check it against the CRC of the bytes in `range` of the underlying buffer.
+    /// Returns `ReadError::Invalid`, with the position of the CRC byte, if they do not match.
+    /// On error the position is left unchanged.
+    /// ```
+    /// # use stream_buf::{ReadError, StreamBufReader};
+    /// // CRSF battery frame: sync, length, type, payload, CRC of type and payload
+    /// let buf = [0xc8, 0x0a, 0x08, 0x00, 0x9a, 0x00, 0x05, 0x00, 0x00, 0x64, 0x46, 0x92];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// sbuf_reader.advance(11);
+    /// assert_eq!(Err(ReadError::Invalid { pos: 11 }), sbuf_reader.verify_crc8(2..10));
+    /// assert_eq!(Ok(()), sbuf_reader.verify_crc8(2..11));
+    /// assert_eq!(12, sbuf_reader.pos());
+    /// ```
+    pub fn verify_crc8(&mut self, range: Range<usize>) -> Result<(), ReadError> {
+        let expected = self.crc(&CRC_8_DVB_S2, range)?;
+        let pos = self.pos();
+        if u32::from(self.peek::<u8>()?) != expected {
+            return Err(ReadError::Invalid { pos });
+        }
+        self.advance(1);
+        Ok(())
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn check_values() {
+        assert_eq!(0xbc, CRC_8_DVB_S2.checksum(CHECK));
+        assert_eq!(0x29b1, CRC_16_CCITT.checksum(CHECK));
+        assert_eq!(0x906e, CRC_16_X25.checksum(CHECK));
+        assert_eq!(0x4b37, CRC_16_MODBUS.checksum(CHECK));
+        assert_eq!(0xcbf4_3926, CRC_32.checksum(CHECK));
+        assert_eq!(0x00, CRC_8_DVB_S2.checksum(&[]));
+        assert_eq!(0x0000_0000, CRC_32.checksum(&[]));
+        assert_eq!(
+            (1, 2, 4),
+            (CRC_8_DVB_S2.byte_len(), CRC_16_CCITT.byte_len(), CRC_32.byte_len())
+        );
+
+        // widths that are not a whole number of bytes
+        assert_eq!(0x19, Crc::new(5, 0x05, 0x1f, true, 0x1f).checksum(CHECK));
+        assert_eq!(0x059e, Crc::new(15, 0x4599, 0x0000, false, 0x0000).checksum(CHECK));
+        assert_eq!(
+            0x21cf02,
+            Crc::new(24, 0x864cfb, 0xb704ce, false, 0x000000).checksum(CHECK)
+        );
+    }
+
+    #[test]
+    fn digest() {
+        let mut digest = CRC_32.digest();
+        digest.update(&CHECK[..4]);
+        assert_eq!(CRC_32.checksum(&CHECK[..4]), digest.finalize());
+        for byte in &CHECK[4..] {
+            digest.update_u8(*byte);
+        }
+        assert_eq!(0xcbf4_3926, digest.finalize());
+    }
+
+    #[test]
+    fn crc_since() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u8(0x24);
+        sbuf.write(CHECK);
+        assert_eq!(0x29b1, sbuf.crc_since(&CRC_16_CCITT, 1));
+        assert_eq!(0xffff, sbuf.crc_since(&CRC_16_CCITT, 10));
+        assert_eq!(0xffff, sbuf.crc_since(&CRC_16_CCITT, 11));
+        let crc = sbuf.crc_since(&CRC_8_DVB_S2, 1);
+        sbuf.write_u8(crc as u8);
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(Ok(0x4b37), sbuf_reader.crc(&CRC_16_MODBUS, 1..10));
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 8,
+                requested: 4,
+                available: 3
+            }),
+            sbuf_reader.crc(&CRC_16_MODBUS, 8..12)
+        );
+        sbuf_reader.advance(10);
+        assert_eq!(Err(ReadError::Invalid { pos: 10 }), sbuf_reader.verify_crc8(0..10));
+        assert_eq!(10, sbuf_reader.pos());
+        assert_eq!(Ok(()), sbuf_reader.verify_crc8(1..10));
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 11,
+                requested: 1,
+                available: 0
+            }),
+            sbuf_reader.verify_crc8(1..10)
+        );
+    }
+}
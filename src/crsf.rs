@@ -0,0 +1,623 @@
+#![allow(unused)]
+
+//! CRSF (Crossfire) frames, as used by TBS Crossfire and ExpressLRS receivers and transmitters.
+//!
+//! A frame is an address (the sync byte), a length covering the type, payload and CRC, a type, the payload and a
+//! CRC-8/DVB-S2 of the type and payload. Frames with a type of 0x28 or above have an extended header: the payload
+//! starts with destination and origin addresses. Multi-byte payload fields are big endian.
+//! ```
+//! # use stream_buf::{CRSF_SYNC_BYTE, CrsfBattery, StreamBufReader, StreamBufWriter};
+//! let mut data = [0u8; 64];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! let battery = CrsfBattery { voltage: 154, current: 5, capacity: 100, remaining: 70 };
+//! sbuf_writer.try_write_crsf(CRSF_SYNC_BYTE, &battery).unwrap();
+//! assert_eq!([0xc8, 0x0a, 0x08, 0x00, 0x9a, 0x00, 0x05, 0x00, 0x00, 0x64, 0x46, 0x92], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! let frame = sbuf_reader.try_read_crsf().unwrap();
+//! assert_eq!(Ok(battery), frame.payload::<CrsfBattery>());
+//! ```
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::{BitOrder, BitWriter};
+use crate::byte_order::{BigEndian, ByteOrder};
+use crate::crc::CRC_8_DVB_S2;
+use crate::encode::{Decode, Encode};
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+/// Sync byte that starts frames sent to the flight controller.
+pub const CRSF_SYNC_BYTE: u8 = 0xc8;
+pub const CRSF_ADDRESS_BROADCAST: u8 = 0x00;
+pub const CRSF_ADDRESS_FLIGHT_CONTROLLER: u8 = 0xc8;
+pub const CRSF_ADDRESS_RADIO_TRANSMITTER: u8 = 0xea;
+pub const CRSF_ADDRESS_RECEIVER: u8 = 0xec;
+pub const CRSF_ADDRESS_TRANSMITTER: u8 = 0xee;
+
+/// Largest number of bytes in a frame, including the address and length.
+const CRSF_FRAME_SIZE_MAX: usize = 64;
+/// Largest payload, including the destination and origin of an extended header.
+const CRSF_PAYLOAD_SIZE_MAX: usize = CRSF_FRAME_SIZE_MAX - 4;
+/// Frame types from this value up have an extended header.
+const CRSF_FRAME_TYPE_EXTENDED_MIN: u8 = 0x28;
+const CRSF_FRAME_TYPE_FLIGHT_MODE: u8 = 0x21;
+/// Number of bits in each packed RC channel.
+const CRSF_RC_CHANNEL_BITS: u32 = 11;
+
+/// Payload of a CRSF frame type.
+pub trait CrsfPayload: Encode + Decode {
+    const FRAME_TYPE: u8;
+}
+
+/// GPS position, type 0x02.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CrsfGps {
+    /// Latitude, degrees * 1e7.
+    pub latitude: i32,
+    /// Longitude, degrees * 1e7.
+    pub longitude: i32,
+    /// Ground speed, km/h * 10.
+    pub groundspeed: u16,
+    /// Heading, degrees * 100.
+    pub heading: u16,
+    /// Altitude, metres + 1000.
+    pub altitude: u16,
+    pub satellites: u8,
+}
+
+/// Battery sensor, type 0x08.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CrsfBattery {
+    /// Voltage, volts * 10.
+    pub voltage: u16,
+    /// Current, amps * 10.
+    pub current: u16,
+    /// Capacity used, mAh. This is sent as 24 bits, so larger values are saturated.
+    pub capacity: u32,
+    /// Remaining charge, percent.
+    pub remaining: u8,
+}
+
+/// Link statistics, type 0x14.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CrsfLinkStatistics {
+    /// Uplink RSSI of antenna 1, -dBm.
+    pub uplink_rssi_1: u8,
+    /// Uplink RSSI of antenna 2, -dBm.
+    pub uplink_rssi_2: u8,
+    /// Uplink link quality, percent.
+    pub uplink_link_quality: u8,
+    /// Uplink signal to noise ratio, dB.
+    pub uplink_snr: i8,
+    pub active_antenna: u8,
+    pub rf_mode: u8,
+    /// Uplink transmit power, as an index into the table of power levels.
+    pub uplink_tx_power: u8,
+    /// Downlink RSSI, -dBm.
+    pub downlink_rssi: u8,
+    /// Downlink link quality, percent.
+    pub downlink_link_quality: u8,
+    /// Downlink signal to noise ratio, dB.
+    pub downlink_snr: i8,
+}
+
+/// RC channels, type 0x16: 16 channels of 11 bits, packed least significant bit first.
+/// The channel values are as sent, 172 to 1811 corresponds to 988us to 2012us.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CrsfRcChannels {
+    pub channels: [u16; 16],
+}
+
+/// Attitude, type 0x1E. Angles are radians * 10000.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CrsfAttitude {
+    pub pitch: i16,
+    pub roll: i16,
+    pub yaw: i16,
+}
+
+impl CrsfPayload for CrsfGps {
+    const FRAME_TYPE: u8 = 0x02;
+}
+
+impl CrsfPayload for CrsfBattery {
+    const FRAME_TYPE: u8 = 0x08;
+}
+
+impl CrsfPayload for CrsfLinkStatistics {
+    const FRAME_TYPE: u8 = 0x14;
+}
+
+impl CrsfPayload for CrsfRcChannels {
+    const FRAME_TYPE: u8 = 0x16;
+}
+
+impl CrsfPayload for CrsfAttitude {
+    const FRAME_TYPE: u8 = 0x1e;
+}
+
+impl Encode for CrsfGps {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.try_write_i32_big_endian(self.latitude)?;
+        writer.try_write_i32_big_endian(self.longitude)?;
+        writer.try_write_u16_big_endian(self.groundspeed)?;
+        writer.try_write_u16_big_endian(self.heading)?;
+        writer.try_write_u16_big_endian(self.altitude)?;
+        writer.try_write_u8(self.satellites)
+    }
+}
+
+impl Decode for CrsfGps {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        Ok(Self {
+            latitude: reader.try_read_i32_big_endian()?,
+            longitude: reader.try_read_i32_big_endian()?,
+            groundspeed: reader.try_read_u16_big_endian()?,
+            heading: reader.try_read_u16_big_endian()?,
+            altitude: reader.try_read_u16_big_endian()?,
+            satellites: reader.try_read_u8()?,
+        })
+    }
+}
+
+impl Encode for CrsfBattery {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.try_write_u16_big_endian(self.voltage)?;
+        writer.try_write_u16_big_endian(self.current)?;
+        writer.try_write(&self.capacity.min(0xff_ffff).to_be_bytes()[1..])?;
+        writer.try_write_u8(self.remaining)
+    }
+}
+
+impl Decode for CrsfBattery {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        let voltage = reader.try_read_u16_big_endian()?;
+        let current = reader.try_read_u16_big_endian()?;
+        let mut capacity = [0u8; 4];
+        reader.try_read(&mut capacity[1..])?;
+        Ok(Self {
+            voltage,
+            current,
+            capacity: u32::from_be_bytes(capacity),
+            remaining: reader.try_read_u8()?,
+        })
+    }
+}
+
+impl Encode for CrsfLinkStatistics {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.try_write(&[
+            self.uplink_rssi_1,
+            self.uplink_rssi_2,
+            self.uplink_link_quality,
+            self.uplink_snr as u8,
+            self.active_antenna,
+            self.rf_mode,
+            self.uplink_tx_power,
+            self.downlink_rssi,
+            self.downlink_link_quality,
+            self.downlink_snr as u8,
+        ])
+    }
+}
+
+impl Decode for CrsfLinkStatistics {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        Ok(Self {
+            uplink_rssi_1: reader.try_read_u8()?,
+            uplink_rssi_2: reader.try_read_u8()?,
+            uplink_link_quality: reader.try_read_u8()?,
+            uplink_snr: reader.try_read_i8()?,
+            active_antenna: reader.try_read_u8()?,
+            rf_mode: reader.try_read_u8()?,
+            uplink_tx_power: reader.try_read_u8()?,
+            downlink_rssi: reader.try_read_u8()?,
+            downlink_link_quality: reader.try_read_u8()?,
+            downlink_snr: reader.try_read_i8()?,
+        })
+    }
+}
+
+/// Channel values are masked to 11 bits.
+impl Encode for CrsfRcChannels {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        let mut bit_writer = BitWriter::new(writer, BitOrder::LsbFirst);
+        for channel in self.channels {
+            bit_writer.try_write_bits(channel.into(), CRSF_RC_CHANNEL_BITS)?;
+        }
+        Ok(())
+    }
+}
+
+impl Decode for CrsfRcChannels {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        let mut bit_reader = BitReader::new(reader, BitOrder::LsbFirst);
+        let mut channels = [0u16; 16];
+        for channel in &mut channels {
+            *channel = bit_reader.try_read_bits(CRSF_RC_CHANNEL_BITS)? as u16;
+        }
+        Ok(Self { channels })
+    }
+}
+
+impl Encode for CrsfAttitude {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.try_write_i16_big_endian(self.pitch)?;
+        writer.try_write_i16_big_endian(self.roll)?;
+        writer.try_write_i16_big_endian(self.yaw)
+    }
+}
+
+impl Decode for CrsfAttitude {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        Ok(Self {
+            pitch: reader.try_read_i16_big_endian()?,
+            roll: reader.try_read_i16_big_endian()?,
+            yaw: reader.try_read_i16_big_endian()?,
+        })
+    }
+}
+
+/// Destination and origin addresses of a frame with an extended header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrsfExtendedHeader {
+    pub destination: u8,
+    pub origin: u8,
+}
+
+/// A CRSF frame whose CRC has been checked, returned by `StreamBufReader::try_read_crsf()`.
+#[derive(Clone)]
+pub struct CrsfFrame<'a> {
+    pub address: u8,
+    pub frame_type: u8,
+    /// The destination and origin, if the frame type has an extended header.
+    pub extended: Option<CrsfExtendedHeader>,
+    /// Reader over the payload, excluding any extended header.
+    pub payload: StreamBufReader<'a, BigEndian>,
+}
+
+impl<'a> CrsfFrame<'a> {
+    /// Return the payload decoded as P. Returns `ReadError::Invalid`, with position 0, if the frame is not of type
+    /// `P::FRAME_TYPE`.
+    pub fn payload<P: CrsfPayload>(&self) -> Result<P, ReadError> {
+        if self.frame_type != P::FRAME_TYPE {
+            return Err(ReadError::Invalid { pos: 0 });
+        }
+        self.payload.clone().get()
+    }
+
+    /// Return the flight mode of a flight mode frame, type 0x21. Returns `ReadError::Invalid`, with position 0, if
+    /// the frame is of another type.
+    pub fn flight_mode(&self) -> Result<&'a str, ReadError> {
+        if self.frame_type != CRSF_FRAME_TYPE_FLIGHT_MODE {
+            return Err(ReadError::Invalid { pos: 0 });
+        }
+        self.payload.clone().read_cstr()
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write a CRSF frame with `payload`, or return a WriteError if there is not enough space available.
+    /// On error nothing is written.
+    pub fn try_write_crsf<P: CrsfPayload>(&mut self, address: u8, payload: &P) -> Result<(), WriteError> {
+        self.try_write_crsf_with(address, P::FRAME_TYPE, |w| w.put(payload))
+    }
+
+    /// Write a CRSF flight mode frame, or return a WriteError if there is not enough space available.
+    /// On error nothing is written.
+    /// ```
+    /// # use stream_buf::{CRSF_SYNC_BYTE, StreamBufReader, StreamBufWriter};
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    /// sbuf_writer.try_write_crsf_flight_mode(CRSF_SYNC_BYTE, "ACRO").unwrap();
+    /// assert_eq!([0xc8, 0x07, 0x21, b'A', b'C', b'R', b'O', 0x00], sbuf_writer.get_ref()[..8]);
+    ///
+    /// let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    /// assert_eq!(Ok("ACRO"), sbuf_reader.try_read_crsf().unwrap().flight_mode());
+    /// ```
+    pub fn try_write_crsf_flight_mode(&mut self, address: u8, flight_mode: &str) -> Result<(), WriteError> {
+        self.try_write_crsf_with(address, CRSF_FRAME_TYPE_FLIGHT_MODE, |w| {
+            w.try_write_str_with_zero_terminator(flight_mode)
+        })
+    }
+
+    /// Write a CRSF frame whose payload is written by `f`, then fill in the length and CRC.
+    ///
+    /// If `f` returns an error, a write within `f` is dropped, or the payload is longer than the 60 bytes allowed by
+    /// CRSF, then the partial frame is rolled back and an error is returned. A payload that is too long is reported as
+    /// a WriteError with `available` set to 60.
+    pub fn try_write_crsf_with<F>(&mut self, address: u8, frame_type: u8, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let result = self.write_crsf_frame(address, frame_type, f);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    /// Write a CRSF frame with an extended header, whose payload is written by `f`.
+    /// This is otherwise the same as `try_write_crsf_with()`.
+    /// ```
+    /// # use stream_buf::{CRSF_ADDRESS_BROADCAST, CRSF_ADDRESS_RADIO_TRANSMITTER, CRSF_SYNC_BYTE, StreamBufWriter};
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// // device ping
+    /// let (destination, origin) = (CRSF_ADDRESS_BROADCAST, CRSF_ADDRESS_RADIO_TRANSMITTER);
+    /// sbuf_writer.try_write_crsf_extended_with(CRSF_SYNC_BYTE, 0x28, destination, origin, |_| Ok(())).unwrap();
+    /// assert_eq!([0xc8, 0x04, 0x28, 0x00, 0xea, 0x54], sbuf_writer.get_ref());
+    /// ```
+    pub fn try_write_crsf_extended_with<F>(
+        &mut self,
+        address: u8,
+        frame_type: u8,
+        destination: u8,
+        origin: u8,
+        f: F,
+    ) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        self.try_write_crsf_with(address, frame_type, |w| {
+            w.try_write(&[destination, origin])?;
+            f(w)
+        })
+    }
+
+    fn write_crsf_frame<F>(&mut self, address: u8, frame_type: u8, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        self.try_write_u8(address)?;
+        let length = self.reserve_u8()?;
+        let start = self.pos();
+        self.try_write_u8(frame_type)?;
+        f(self)?;
+        self.check_no_drops_since(&checkpoint)?;
+        let len = self.pos() - start - 1;
+        if len > CRSF_PAYLOAD_SIZE_MAX {
+            return Err(WriteError {
+                pos: start + 1,
+                requested: len,
+                available: CRSF_PAYLOAD_SIZE_MAX,
+            });
+        }
+        self.fill_placeholder(length, (len + 2) as u8)?;
+        let crc = self.crc_since(&CRC_8_DVB_S2, start);
+        self.try_write_u8(crc as u8)
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Return the CRSF frame at the current position and advance past it.
+    ///
+    /// Returns `ReadError::Invalid` with the position of the length byte if the length is out of range, or with the
+    /// position of the CRC if the CRC does not match. On error the position is left unchanged.
+    /// ```
+    /// # use stream_buf::{CrsfAttitude, CrsfBattery, ReadError, StreamBufReader};
+    /// let buf = [
+    ///     0xc8, 0x08, 0x1e, 0x01, 0xf4, 0xfe, 0x0c, 0x3d, 0x54, 0xec,
+    ///     0xc8, 0x08, 0x1e, 0x01, 0xf4, 0xfe, 0x0c, 0x3d, 0x54, 0xed,
+    /// ];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// let frame = sbuf_reader.try_read_crsf().unwrap();
+    /// assert_eq!(0x1e, frame.frame_type);
+    /// assert_eq!(Ok(CrsfAttitude { pitch: 500, roll: -500, yaw: 15700 }), frame.payload());
+    /// assert_eq!(Err(ReadError::Invalid { pos: 0 }), frame.payload::<CrsfBattery>());
+    ///
+    /// assert_eq!(Some(ReadError::Invalid { pos: 19 }), sbuf_reader.try_read_crsf().err());
+    /// assert_eq!(10, sbuf_reader.pos());
+    /// ```
+    pub fn try_read_crsf(&mut self) -> Result<CrsfFrame<'a>, ReadError> {
+        let mut reader = self.clone();
+        let frame = reader.read_crsf_frame()?;
+        *self = reader;
+        Ok(frame)
+    }
+
+    fn read_crsf_frame(&mut self) -> Result<CrsfFrame<'a>, ReadError> {
+        let address = self.try_read_u8()?;
+        let length_pos = self.pos();
+        let len = usize::from(self.try_read_u8()?);
+        if !(2..=CRSF_PAYLOAD_SIZE_MAX + 2).contains(&len) {
+            return Err(ReadError::Invalid { pos: length_pos });
+        }
+        let start = self.pos();
+        let mut body = self.try_take(len - 1)?;
+        self.verify_crc8(start..start + len - 1)?;
+
+        let frame_type = body.try_read_u8()?;
+        let extended = if frame_type >= CRSF_FRAME_TYPE_EXTENDED_MIN {
+            let [destination, origin] = body
+                .get::<[u8; 2]>()
+                .map_err(|_| ReadError::Invalid { pos: length_pos })?;
+            Some(CrsfExtendedHeader { destination, origin })
+        } else {
+            None
+        };
+        Ok(CrsfFrame {
+            address,
+            frame_type,
+            extended,
+            payload: StreamBufReader::with_byte_order(body.remaining_slice(), BigEndian),
+        })
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rc_channels() -> Result<(), ReadError> {
+        let mut rc_channels = CrsfRcChannels::default();
+        for (index, channel) in rc_channels.channels.iter_mut().enumerate() {
+            *channel = 172 + 100 * index as u16;
+        }
+        let mut data = [0u8; 64];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_crsf(CRSF_SYNC_BYTE, &rc_channels));
+        assert_eq!(26, sbuf.bytes_written());
+        // channel 0 is 0x0ac, channel 1 is 0x110
+        assert_eq!([0xc8, 0x18, 0x16, 0xac, 0x80, 0x08], sbuf.get_ref()[..6]);
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        let frame = sbuf_reader.try_read_crsf()?;
+        assert_eq!(Ok(rc_channels), frame.payload());
+        assert!(sbuf_reader.is_full());
+        Ok(())
+    }
+
+    #[test]
+    fn telemetry() -> Result<(), ReadError> {
+        let gps = CrsfGps {
+            latitude: 515_014_760,
+            longitude: -1_419_260,
+            groundspeed: 123,
+            heading: 27_000,
+            altitude: 1_120,
+            satellites: 11,
+        };
+        let link_statistics = CrsfLinkStatistics {
+            uplink_rssi_1: 60,
+            uplink_rssi_2: 62,
+            uplink_link_quality: 100,
+            uplink_snr: -3,
+            active_antenna: 1,
+            rf_mode: 2,
+            uplink_tx_power: 3,
+            downlink_rssi: 70,
+            downlink_link_quality: 98,
+            downlink_snr: 5,
+        };
+        let battery = CrsfBattery {
+            capacity: 0x0100_0000,
+            ..Default::default()
+        };
+        let mut data = [0u8; 64];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_crsf(CRSF_SYNC_BYTE, &gps));
+        assert_eq!(
+            [0xc8, 0x11, 0x02, 0x1e, 0xb2, 0x80, 0x68, 0xff, 0xea, 0x58, 0x04],
+            sbuf.get_ref()[..11]
+        );
+        assert_eq!(Ok(()), sbuf.try_write_crsf(CRSF_ADDRESS_RECEIVER, &link_statistics));
+        assert_eq!(Ok(()), sbuf.try_write_crsf(CRSF_SYNC_BYTE, &battery));
+        assert_eq!(19 + 14 + 12, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(Ok(gps), sbuf_reader.try_read_crsf()?.payload());
+        let frame = sbuf_reader.try_read_crsf()?;
+        assert_eq!(CRSF_ADDRESS_RECEIVER, frame.address);
+        assert_eq!(Ok(link_statistics), frame.payload());
+        let battery = sbuf_reader.try_read_crsf()?.payload::<CrsfBattery>()?;
+        assert_eq!(0x00ff_ffff, battery.capacity);
+        Ok(())
+    }
+
+    #[test]
+    fn extended_and_errors() {
+        let mut data = [0u8; 72];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Ok(()),
+            sbuf.try_write_crsf_extended_with(
+                CRSF_SYNC_BYTE,
+                0x2c,
+                CRSF_ADDRESS_TRANSMITTER,
+                CRSF_ADDRESS_RADIO_TRANSMITTER,
+                |w| w.try_write(&[0x01, 0x00])
+            )
+        );
+        assert_eq!([0xc8, 0x06, 0x2c, 0xee, 0xea, 0x01, 0x00], sbuf.get_ref()[..7]);
+
+        // the payload may not be longer than 60 bytes
+        assert_eq!(
+            Err(WriteError {
+                pos: 11,
+                requested: 61,
+                available: 60
+            }),
+            sbuf.try_write_crsf_with(CRSF_SYNC_BYTE, 0x0f, |w| w.try_fill(0, 61))
+        );
+        assert_eq!(
+            Err(WriteError {
+                pos: 11,
+                requested: 62,
+                available: 61
+            }),
+            sbuf.try_write_crsf_with(CRSF_SYNC_BYTE, 0x0f, |w| w.try_fill(0, 62))
+        );
+        assert!(!sbuf.overflowed());
+        assert_eq!(
+            Ok(()),
+            sbuf.try_write_crsf_with(CRSF_SYNC_BYTE, 0x0f, |w| w.try_fill(0, 60))
+        );
+        assert!(sbuf.is_full());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        let frame = sbuf_reader.try_read_crsf();
+        assert_eq!(
+            Ok((
+                0x2c,
+                Some(CrsfExtendedHeader {
+                    destination: CRSF_ADDRESS_TRANSMITTER,
+                    origin: CRSF_ADDRESS_RADIO_TRANSMITTER
+                }),
+                [0x01, 0x00].as_slice()
+            )),
+            frame.map(|frame| (frame.frame_type, frame.extended, frame.payload.remaining_slice()))
+        );
+        assert_eq!(
+            Ok(60),
+            sbuf_reader.try_read_crsf().map(|frame| frame.payload.bytes_remaining())
+        );
+
+        let buf = [0xc8, 0x01, 0x16, 0x00, 0xc8, 0x3f];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Some(ReadError::Invalid { pos: 1 }), sbuf_reader.try_read_crsf().err());
+        // extended frame too short to hold the destination and origin
+        let buf = [0xc8, 0x03, 0x28, 0x00, CRC_8_DVB_S2.checksum(&[0x28, 0x00]) as u8];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Some(ReadError::Invalid { pos: 1 }), sbuf_reader.try_read_crsf().err());
+        let buf = [0xc8, 0x04, 0x28, 0x00];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(
+            Some(ReadError::Truncated {
+                pos: 2,
+                requested: 3,
+                available: 2
+            }),
+            sbuf_reader.try_read_crsf().err()
+        );
+        assert_eq!(0, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn dropped_write_after_overflow() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.fill(0, 9);
+        assert!(sbuf.overflowed());
+
+        // a write dropped within the payload is detected even though the overflow flag was already set
+        assert_eq!(
+            Err(WriteError {
+                pos: 4,
+                requested: 40,
+                available: 4
+            }),
+            sbuf.try_write_crsf_with(CRSF_SYNC_BYTE, 0x10, |w| {
+                w.write_u8(1);
+                w.write(&[0; 40]);
+                Ok(())
+            })
+        );
+        assert!(sbuf.is_empty());
+    }
+}
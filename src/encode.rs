@@ -0,0 +1,303 @@
+#![allow(unused)]
+
+//! `Encode` and `Decode` traits, used by `StreamBufWriter::put` and `StreamBufReader::get`.
+//!
+//! Implementations are provided for the primitive types, `bool`, arrays, tuples and `Option`.
+//! Multi-byte values use the byte order of the stream_buf, consistent with `write_u16`, `read_u16` etc.
+//! User types can be serialized by composing the existing implementations:
+//! ```
+//! # use stream_buf::{ByteOrder, Decode, Encode, ReadError, StreamBufReader, StreamBufWriter, WriteError};
+//! #[derive(Debug, Default, PartialEq)]
+//! struct Sample {
+//!     gyro: [i16; 3],
+//!     timestamp_us: u64,
+//! }
+//!
+//! impl Encode for Sample {
+//!     fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+//!         writer.put(&self.gyro)?;
+//!         writer.put(&self.timestamp_us)
+//!     }
+//! }
+//!
+//! impl Decode for Sample {
+//!     fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+//!         Ok(Self { gyro: reader.get()?, timestamp_us: reader.get()? })
+//!     }
+//! }
+//!
+//! let sample = Sample { gyro: [1, -2, 3], timestamp_us: 123_456 };
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.put(&sample).unwrap();
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!(Ok(sample), sbuf_reader.get::<Sample>());
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::mem::MaybeUninit;
+
+/// A type that can be serialized to a `StreamBufWriter`.
+pub trait Encode {
+    /// Write `self` to the stream_buf.
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError>;
+}
+
+/// A type that can be deserialized from a `StreamBufReader`.
+pub trait Decode: Sized {
+    /// Read a value from the stream_buf.
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError>;
+}
+
+macro_rules! impl_encode_decode_primitive {
+    ($type:ty, $try_write:ident, $try_read:ident) => {
+        impl Encode for $type {
+            fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+                writer.$try_write(*self)
+            }
+        }
+
+        impl Decode for $type {
+            fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+                reader.$try_read()
+            }
+        }
+    };
+}
+
+impl_encode_decode_primitive!(u8, try_write_u8, try_read_u8);
+impl_encode_decode_primitive!(i8, try_write_i8, try_read_i8);
+impl_encode_decode_primitive!(u16, try_write_u16, try_read_u16);
+impl_encode_decode_primitive!(i16, try_write_i16, try_read_i16);
+impl_encode_decode_primitive!(u32, try_write_u32, try_read_u32);
+impl_encode_decode_primitive!(i32, try_write_i32, try_read_i32);
+impl_encode_decode_primitive!(u64, try_write_u64, try_read_u64);
+impl_encode_decode_primitive!(i64, try_write_i64, try_read_i64);
+impl_encode_decode_primitive!(u128, try_write_u128, try_read_u128);
+impl_encode_decode_primitive!(i128, try_write_i128, try_read_i128);
+impl_encode_decode_primitive!(f32, try_write_f32, try_read_f32);
+impl_encode_decode_primitive!(f64, try_write_f64, try_read_f64);
+
+/// A bool is encoded as a single byte, 0 or 1. Any other byte is rejected as `ReadError::Invalid` when decoded.
+impl Encode for bool {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.try_write_u8(u8::from(*self))
+    }
+}
+
+impl Decode for bool {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        let pos = reader.pos();
+        match reader.try_read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ReadError::Invalid { pos }),
+        }
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        (**self).encode(writer)
+    }
+}
+
+/// A slice is encoded element by element, without a length prefix.
+impl<T: Encode> Encode for [T] {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        self.iter().try_for_each(|item| item.encode(writer))
+    }
+}
+
+/// An array is encoded element by element, without a length prefix.
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        self.as_slice().encode(writer)
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        let mut array: [MaybeUninit<T>; N] = [const { MaybeUninit::uninit() }; N];
+        for index in 0..N {
+            match T::decode(reader) {
+                Ok(item) => {
+                    array[index].write(item);
+                }
+                Err(error) => {
+                    for item in &mut array[..index] {
+                        // SAFETY: the elements before `index` have been initialized
+                        unsafe { item.assume_init_drop() };
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        // SAFETY: every element has been initialized
+        Ok(array.map(|item| unsafe { item.assume_init() }))
+    }
+}
+
+/// An Option is encoded as a u8 tag, 0 for None and 1 for Some, followed by the value if present.
+/// Any other tag is rejected as `ReadError::Invalid` when decoded.
+impl<T: Encode> Encode for Option<T> {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        match self {
+            None => writer.try_write_u8(0),
+            Some(value) => {
+                writer.try_write_u8(1)?;
+                value.encode(writer)
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        let pos = reader.pos();
+        match reader.try_read_u8()? {
+            0 => Ok(None),
+            1 => T::decode(reader).map(Some),
+            _ => Err(ReadError::Invalid { pos }),
+        }
+    }
+}
+
+macro_rules! impl_encode_decode_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: Encode),+> Encode for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+                let ($($name,)+) = self;
+                $($name.encode(writer)?;)+
+                Ok(())
+            }
+        }
+
+        impl<$($name: Decode),+> Decode for ($($name,)+) {
+            fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+                Ok(($($name::decode(reader)?,)+))
+            }
+        }
+    };
+}
+
+impl_encode_decode_tuple!(T0);
+impl_encode_decode_tuple!(T0 T1);
+impl_encode_decode_tuple!(T0 T1 T2);
+impl_encode_decode_tuple!(T0 T1 T2 T3);
+impl_encode_decode_tuple!(T0 T1 T2 T3 T4);
+impl_encode_decode_tuple!(T0 T1 T2 T3 T4 T5);
+impl_encode_decode_tuple!(T0 T1 T2 T3 T4 T5 T6);
+impl_encode_decode_tuple!(T0 T1 T2 T3 T4 T5 T6 T7);
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn put_get_primitives() {
+        let mut data = [0u8; 64];
+        let mut sbuf_writer = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf_writer.put(&0x12u8));
+        assert_eq!(Ok(()), sbuf_writer.put(&-2i16));
+        assert_eq!(Ok(()), sbuf_writer.put(&0x0a1b2c3du32));
+        assert_eq!(Ok(()), sbuf_writer.put(&1234.56f32));
+        assert_eq!(Ok(()), sbuf_writer.put(&-1234.56f64));
+        assert_eq!(Ok(()), sbuf_writer.put(&true));
+        assert_eq!(Ok(()), sbuf_writer.put(&u64::MAX));
+        assert_eq!([0x12, 0xfe, 0xff, 0x3d, 0x2c, 0x1b, 0x0a], sbuf_writer.get_ref()[0..7]);
+
+        let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+        assert_eq!(Ok(0x12), sbuf_reader.get::<u8>());
+        assert_eq!(Ok(-2), sbuf_reader.get::<i16>());
+        assert_eq!(Ok(0x0a1b2c3d), sbuf_reader.get::<u32>());
+        assert_eq!(Ok(1234.56), sbuf_reader.get::<f32>());
+        assert_eq!(Ok(-1234.56), sbuf_reader.get::<f64>());
+        assert_eq!(Ok(true), sbuf_reader.get::<bool>());
+        assert_eq!(Ok(u64::MAX), sbuf_reader.get::<u64>());
+        assert_eq!(0, sbuf_reader.bytes_remaining());
+    }
+
+    #[test]
+    fn put_get_compound() {
+        let mut data = [0u8; 64];
+        let mut sbuf_writer = StreamBufWriter::new(&mut data);
+        let array: [i16; 3] = [1, -2, 3];
+        let tuple: (u8, u32, bool) = (7, 0xdeadbeef, false);
+        assert_eq!(Ok(()), sbuf_writer.put(&array));
+        assert_eq!(Ok(()), sbuf_writer.put(&tuple));
+        assert_eq!(Ok(()), sbuf_writer.put(&Some(0x55u8)));
+        assert_eq!(Ok(()), sbuf_writer.put(&None::<u32>));
+        assert_eq!(Ok(()), sbuf_writer.put(&[(1u8, 2u8), (3u8, 4u8)][..]));
+        assert_eq!(6 + 6 + 2 + 1 + 4, sbuf_writer.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+        assert_eq!(Ok(array), sbuf_reader.get::<[i16; 3]>());
+        assert_eq!(Ok(tuple), sbuf_reader.get::<(u8, u32, bool)>());
+        assert_eq!(Ok(Some(0x55)), sbuf_reader.get::<Option<u8>>());
+        assert_eq!(Ok(None), sbuf_reader.get::<Option<u32>>());
+        assert_eq!(Ok([[1, 2], [3, 4]]), sbuf_reader.get::<[[u8; 2]; 2]>());
+    }
+
+    #[test]
+    fn get_invalid_tag() {
+        let buf = [0x02, 0x01, 0x02, 0xff, 0x01, 0x05];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Err(ReadError::Invalid { pos: 0 }), sbuf_reader.get::<bool>());
+        assert_eq!(0, sbuf_reader.pos());
+        assert_eq!(Ok([0x02, 0x01]), sbuf_reader.get::<[u8; 2]>());
+        assert_eq!(Err(ReadError::Invalid { pos: 3 }), sbuf_reader.get::<(u8, Option<u8>)>());
+        assert_eq!(2, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn get_array_without_default() {
+        let mut buf = [0u8; 130];
+        buf[0] = 1;
+        buf[129] = 2;
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        let array = sbuf_reader.get::<[[u8; 64]; 2]>();
+        assert_eq!(Ok((1, 0, 0)), array.map(|array| (array[0][0], array[0][63], array[1][63])));
+        assert_eq!(Ok([0x00, 0x02]), sbuf_reader.get::<[u8; 2]>());
+
+        // an error part way through the array leaves the position unchanged
+        let mut sbuf_reader = StreamBufReader::new(&buf[..100]);
+        assert!(sbuf_reader.get::<[[u8; 64]; 2]>().is_err());
+        assert_eq!(0, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn put_get_failure_does_not_advance() {
+        let mut data = [0u8; 5];
+        let mut sbuf_writer = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf_writer.put(&1u8));
+        assert_eq!(
+            Err(WriteError {
+                pos: 3,
+                requested: 4,
+                available: 2
+            }),
+            sbuf_writer.put(&(2u16, 3u32))
+        );
+        assert_eq!(1, sbuf_writer.bytes_written());
+        assert!(sbuf_writer.overflowed());
+        assert_eq!(Ok(()), sbuf_writer.put(&(2u16, 3u16)));
+
+        let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+        assert_eq!(Ok(1), sbuf_reader.get::<u8>());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 3,
+                requested: 4,
+                available: 2
+            }),
+            sbuf_reader.get::<(u16, u32)>()
+        );
+        assert_eq!(1, sbuf_reader.pos());
+        assert_eq!(Ok([2, 3]), sbuf_reader.get::<[u16; 2]>());
+    }
+}
@@ -0,0 +1,262 @@
+#![allow(unused)]
+
+//! Floating point values carried as scaled integers.
+//!
+//! Many wire formats carry floats as integers, for example an angle in hundredths of a degree in an i16, or a latitude
+//! in units of 1e-7 degrees in an i32. The `write_*_scaled` functions multiply the value by the scale, round it to the
+//! nearest integer, with halves rounded away from zero, and saturate it to the range of the integer type, so that
+//! out of range values are written as the nearest representable value. NaN is written as zero.
+//!
+//! Q15 and Q31 fractions in the range -1 to 1 are written with a scale of 2^15 and 2^31 respectively.
+//! ```
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//!
+//! sbuf_writer.write_f32_as_i16_scaled(-12.345, 100.0);
+//! sbuf_writer.write_f64_as_i32_scaled(51.4778361, 1.0e7);
+//! sbuf_writer.write_f32_as_q15(1.0);
+//! assert_eq!([0x2d, 0xfb, 0xf9, 0xe4, 0xae, 0x1e, 0xff, 0x7f], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!(-12.35, sbuf_reader.read_f32_from_i16_scaled(100.0));
+//! assert_eq!(51.4778361, sbuf_reader.read_f64_from_i32_scaled(1.0e7));
+//! assert_eq!(32767.0 / 32768.0, sbuf_reader.read_f32_from_q15());
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+/// Scale of a Q15 fraction, 2^15.
+const Q15_SCALE: f32 = 32_768.0;
+/// Scale of a Q31 fraction, 2^31.
+const Q31_SCALE: f64 = 2_147_483_648.0;
+
+/// Magnitude from which every f64 is an integer, 2^52.
+const F64_INTEGER_MIN: f64 = 4_503_599_627_370_496.0;
+
+/// Return `value * scale` rounded to the nearest integer, with halves rounded away from zero.
+/// Converting the result to an integer with `as` saturates it, and converts NaN to zero.
+/// This is also used by the code generated for `#[stream_buf(scale = ..., repr = ...)]` fields.
+#[doc(hidden)]
+pub fn round_scaled(value: f64, scale: f64) -> f64 {
+    let scaled = value * scale;
+    if scaled.is_nan() || scaled.abs() >= F64_INTEGER_MIN {
+        return scaled;
+    }
+    // the fraction is exact, so unlike adding 0.5 this does not round twice
+    let truncated = scaled as i64 as f64;
+    let fraction = scaled - truncated;
+    if fraction >= 0.5 {
+        truncated + 1.0
+    } else if fraction <= -0.5 {
+        truncated - 1.0
+    } else {
+        truncated
+    }
+}
+
+/// Return `value * scale` rounded to the nearest integer and saturated to the range of an i16.
+/// NaN is converted to zero.
+pub(crate) fn scale_to_i16(value: f32, scale: f32) -> i16 {
+    round_scaled(f64::from(value), f64::from(scale)) as i16
+}
+
+/// Generate `try_write_<float>_as_<int>_scaled` and its infallible counterpart.
+macro_rules! write_scaled {
+    ($float:ty, $int:ty, $try_write_int:ident, $try_write:ident, $write:ident) => {
+        #[doc = concat!("Write `value * scale`, rounded and saturated, as an ", stringify!($int), ",")]
+        #[doc = "or return a WriteError if there is not enough space available."]
+        pub fn $try_write(&mut self, value: $float, scale: $float) -> Result<(), WriteError> {
+            self.$try_write_int(round_scaled(f64::from(value), f64::from(scale)) as $int)
+        }
+
+        #[doc = concat!("Write `value * scale`, rounded and saturated, as an ", stringify!($int), ".")]
+        #[doc = "The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write(&mut self, value: $float, scale: $float) {
+            let _ = self.$try_write(value, scale);
+        }
+    };
+}
+
+/// Generate `try_read_<float>_from_<int>_scaled` and its infallible counterpart.
+macro_rules! read_scaled {
+    ($float:ty, $int:ty, $try_read_int:ident, $try_read:ident, $read:ident) => {
+        #[doc = concat!("Return an ", stringify!($int), " divided by `scale`,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read(&mut self, scale: $float) -> Result<$float, ReadError> {
+            self.$try_read_int().map(|value| value as $float / scale)
+        }
+
+        #[doc = concat!("Return an ", stringify!($int), " divided by `scale`,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $read(&mut self, scale: $float) -> $float {
+            self.$try_read(scale).unwrap_or(0.0)
+        }
+    };
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    write_scaled!(
+        f32,
+        i16,
+        try_write_i16,
+        try_write_f32_as_i16_scaled,
+        write_f32_as_i16_scaled
+    );
+    write_scaled!(
+        f32,
+        u16,
+        try_write_u16,
+        try_write_f32_as_u16_scaled,
+        write_f32_as_u16_scaled
+    );
+    write_scaled!(
+        f32,
+        i32,
+        try_write_i32,
+        try_write_f32_as_i32_scaled,
+        write_f32_as_i32_scaled
+    );
+    write_scaled!(
+        f64,
+        i32,
+        try_write_i32,
+        try_write_f64_as_i32_scaled,
+        write_f64_as_i32_scaled
+    );
+
+    /// Write `value` as a Q15 fraction, saturated to the range -1 to 1 - 2^-15,
+    /// or return a WriteError if there is not enough space available.
+    pub fn try_write_f32_as_q15(&mut self, value: f32) -> Result<(), WriteError> {
+        self.try_write_f32_as_i16_scaled(value, Q15_SCALE)
+    }
+
+    /// Write `value` as a Q15 fraction, saturated to the range -1 to 1 - 2^-15.
+    /// The value is dropped and the overflow flag set if there is not enough space available.
+    pub fn write_f32_as_q15(&mut self, value: f32) {
+        let _ = self.try_write_f32_as_q15(value);
+    }
+
+    /// Write `value` as a Q31 fraction, saturated to the range -1 to 1 - 2^-31,
+    /// or return a WriteError if there is not enough space available.
+    pub fn try_write_f32_as_q31(&mut self, value: f32) -> Result<(), WriteError> {
+        self.try_write_f64_as_i32_scaled(f64::from(value), Q31_SCALE)
+    }
+
+    /// Write `value` as a Q31 fraction, saturated to the range -1 to 1 - 2^-31.
+    /// The value is dropped and the overflow flag set if there is not enough space available.
+    pub fn write_f32_as_q31(&mut self, value: f32) {
+        let _ = self.try_write_f32_as_q31(value);
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    read_scaled!(
+        f32,
+        i16,
+        try_read_i16,
+        try_read_f32_from_i16_scaled,
+        read_f32_from_i16_scaled
+    );
+    read_scaled!(
+        f32,
+        u16,
+        try_read_u16,
+        try_read_f32_from_u16_scaled,
+        read_f32_from_u16_scaled
+    );
+    read_scaled!(
+        f32,
+        i32,
+        try_read_i32,
+        try_read_f32_from_i32_scaled,
+        read_f32_from_i32_scaled
+    );
+    read_scaled!(
+        f64,
+        i32,
+        try_read_i32,
+        try_read_f64_from_i32_scaled,
+        read_f64_from_i32_scaled
+    );
+
+    /// Return a Q15 fraction, or a ReadError if there are not enough bytes remaining.
+    pub fn try_read_f32_from_q15(&mut self) -> Result<f32, ReadError> {
+        self.try_read_f32_from_i16_scaled(Q15_SCALE)
+    }
+
+    /// Return a Q15 fraction, or zero if there are not enough bytes remaining.
+    pub fn read_f32_from_q15(&mut self) -> f32 {
+        self.try_read_f32_from_q15().unwrap_or(0.0)
+    }
+
+    /// Return a Q31 fraction, or a ReadError if there are not enough bytes remaining.
+    pub fn try_read_f32_from_q31(&mut self) -> Result<f32, ReadError> {
+        self.try_read_f64_from_i32_scaled(Q31_SCALE).map(|value| value as f32)
+    }
+
+    /// Return a Q31 fraction, or zero if there are not enough bytes remaining.
+    pub fn read_f32_from_q31(&mut self) -> f32 {
+        self.try_read_f32_from_q31().unwrap_or(0.0)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale() {
+        assert_eq!(3, scale_to_i16(0.25, 10.0));
+        assert_eq!(-3, scale_to_i16(-0.25, 10.0));
+        assert_eq!(0, scale_to_i16(0.49999997, 1.0));
+        assert_eq!(0.0, round_scaled(0.49999999999999994, 1.0));
+        assert_eq!(-3.0, round_scaled(-2.5, 1.0));
+        assert_eq!(4_503_599_627_370_497.0, round_scaled(4_503_599_627_370_497.0, 1.0));
+        assert_eq!(i16::MAX, scale_to_i16(1.0e6, 1.0));
+        assert_eq!(i16::MIN, scale_to_i16(f32::NEG_INFINITY, 1.0));
+        assert_eq!(0, scale_to_i16(f32::NAN, 1.0));
+    }
+
+    #[test]
+    fn write_read_scaled() -> Result<(), ReadError> {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_f32_as_u16_scaled(-1.0, 100.0);
+        sbuf.write_f32_as_u16_scaled(16.8, 100.0);
+        sbuf.write_f32_as_i32_scaled(-3.0e9, 1.0);
+        sbuf.write_f32_as_q31(-1.0);
+        sbuf.write_f32_as_q15(-0.5);
+        assert_eq!(
+            [
+                0x00, 0x00, 0x90, 0x06, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0xc0
+            ],
+            sbuf.get_ref()
+        );
+        assert_eq!(
+            Err(WriteError {
+                pos: 14,
+                requested: 4,
+                available: 2
+            }),
+            sbuf.try_write_f64_as_i32_scaled(0.0, 1.0)
+        );
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(0.0, sbuf_reader.try_read_f32_from_u16_scaled(100.0)?);
+        assert_eq!(16.8, sbuf_reader.try_read_f32_from_u16_scaled(100.0)?);
+        assert_eq!(i32::MIN as f32, sbuf_reader.try_read_f32_from_i32_scaled(1.0)?);
+        assert_eq!(-1.0, sbuf_reader.try_read_f32_from_q31()?);
+        assert_eq!(-0.5, sbuf_reader.try_read_f32_from_q15()?);
+        assert!(sbuf_reader.try_read_f32_from_q15().is_err());
+        assert_eq!(0.0, sbuf_reader.read_f64_from_i32_scaled(1.0e7));
+
+        let mut data = [0u8; 4];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_f64_as_i32_scaled(0.49999999999999994, 1.0);
+        assert_eq!([0x00, 0x00, 0x00, 0x00], sbuf.get_ref());
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+#![allow(unused)]
+
+//! 16-bit floating point formats: IEEE 754 half precision (f16) and bfloat16.
+//!
+//! Both are written from and read into an f32. f16 has a 5-bit exponent and 10-bit mantissa, so a range of about
+//! 6.1e-5 to 65504 with 3 significant figures; values too large for it are written as infinity. bfloat16 is the top
+//! half of an f32, so it has the range of an f32 with 2 significant figures. Values are rounded to the nearest
+//! representable value, with ties to even, and NaN is written as a quiet NaN.
+//! ```
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! let mut data = [0u8; 8];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//!
+//! sbuf_writer.write_f16(-2.5);
+//! sbuf_writer.write_bf16(1.0e10);
+//! assert_eq!([0x00, 0xc1, 0x15, 0x50], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!(-2.5, sbuf_reader.read_f16());
+//! assert_eq!(9_999_220_736.0, sbuf_reader.read_bf16());
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+const F32_SIGN: u32 = 0x8000_0000;
+const F32_EXPONENT_BIAS: i32 = 127;
+const F32_MANTISSA_BITS: u32 = 23;
+const F32_MANTISSA_MASK: u32 = 0x007f_ffff;
+const F32_EXPONENT_MAX: i32 = 0xff;
+const F16_EXPONENT_BIAS: i32 = 15;
+const F16_MANTISSA_BITS: u32 = 10;
+const F16_MANTISSA_MASK: u16 = 0x03ff;
+const F16_EXPONENT_MAX: i32 = 0x1f;
+const F16_INFINITY: u16 = 0x7c00;
+const F16_QUIET_NAN: u16 = 0x0200;
+/// Value of the least significant bit of an f16 subnormal, 2^-24.
+const F16_SUBNORMAL_UNIT: f32 = 1.0 / 16_777_216.0;
+const BF16_QUIET_NAN: u32 = 0x0040;
+
+/// Return `mantissa >> shift`, rounded to nearest with ties to even. `shift` must be in the range 1 to 31.
+fn shift_right_rounded(mantissa: u32, shift: u32) -> u32 {
+    let truncated = mantissa >> shift;
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    if remainder > halfway || (remainder == halfway && truncated & 1 != 0) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// Return the bits of the f16 nearest to `value`.
+pub(crate) fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits & F32_SIGN) >> 16) as u16;
+    let mantissa = bits & F32_MANTISSA_MASK;
+    let f32_exponent = ((bits >> F32_MANTISSA_BITS) & 0xff) as i32;
+    if f32_exponent == F32_EXPONENT_MAX {
+        let nan = if mantissa == 0 { 0 } else { F16_QUIET_NAN };
+        return sign | F16_INFINITY | nan;
+    }
+    let exponent = f32_exponent - F32_EXPONENT_BIAS + F16_EXPONENT_BIAS;
+    if exponent >= F16_EXPONENT_MAX {
+        return sign | F16_INFINITY;
+    }
+    let shift = F32_MANTISSA_BITS - F16_MANTISSA_BITS;
+    if exponent > 0 {
+        // a carry out of the mantissa correctly increments the exponent, possibly to infinity
+        let half = ((exponent as u32) << F16_MANTISSA_BITS) + shift_right_rounded(mantissa, shift);
+        return sign | half as u16;
+    }
+    if exponent < -(F16_MANTISSA_BITS as i32) {
+        // less than half the smallest subnormal, so rounds to zero
+        return sign;
+    }
+    // subnormal, including the implicit leading one
+    let half = shift_right_rounded(
+        mantissa | (1 << F32_MANTISSA_BITS),
+        (shift as i32 + 1 - exponent) as u32,
+    );
+    sign | half as u16
+}
+
+/// Return the value of the f16 with the given `bits`.
+pub(crate) fn f16_bits_to_f32(bits: u16) -> f32 {
+    let sign = u32::from(bits) << 16 & F32_SIGN;
+    let exponent = i32::from(bits >> F16_MANTISSA_BITS) & F16_EXPONENT_MAX;
+    let mantissa = u32::from(bits & F16_MANTISSA_MASK) << (F32_MANTISSA_BITS - F16_MANTISSA_BITS);
+    match exponent {
+        0 => {
+            let magnitude = f32::from(bits & F16_MANTISSA_MASK) * F16_SUBNORMAL_UNIT;
+            if sign == 0 { magnitude } else { -magnitude }
+        }
+        F16_EXPONENT_MAX => f32::from_bits(sign | (F32_EXPONENT_MAX as u32) << F32_MANTISSA_BITS | mantissa),
+        _ => {
+            let exponent = (exponent - F16_EXPONENT_BIAS + F32_EXPONENT_BIAS) as u32;
+            f32::from_bits(sign | exponent << F32_MANTISSA_BITS | mantissa)
+        }
+    }
+}
+
+/// Return the bits of the bfloat16 nearest to `value`.
+pub(crate) fn f32_to_bf16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    if value.is_nan() {
+        return ((bits >> 16) | BF16_QUIET_NAN) as u16;
+    }
+    // a carry out of the mantissa correctly increments the exponent, possibly to infinity
+    shift_right_rounded(bits, 16) as u16
+}
+
+/// Return the value of the bfloat16 with the given `bits`.
+pub(crate) fn bf16_bits_to_f32(bits: u16) -> f32 {
+    f32::from_bits(u32::from(bits) << 16)
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write `value` as an IEEE 754 half precision float,
+    /// or return a WriteError if there is not enough space available.
+    pub fn try_write_f16(&mut self, value: f32) -> Result<(), WriteError> {
+        self.try_write_u16(f32_to_f16_bits(value))
+    }
+
+    /// Write `value` as an IEEE 754 half precision float.
+    /// The value is dropped and the overflow flag set if there is not enough space available.
+    pub fn write_f16(&mut self, value: f32) {
+        let _ = self.try_write_f16(value);
+    }
+
+    /// Write `value` as a bfloat16, or return a WriteError if there is not enough space available.
+    pub fn try_write_bf16(&mut self, value: f32) -> Result<(), WriteError> {
+        self.try_write_u16(f32_to_bf16_bits(value))
+    }
+
+    /// Write `value` as a bfloat16.
+    /// The value is dropped and the overflow flag set if there is not enough space available.
+    pub fn write_bf16(&mut self, value: f32) {
+        let _ = self.try_write_bf16(value);
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Return an IEEE 754 half precision float, or a ReadError if there are not enough bytes remaining.
+    pub fn try_read_f16(&mut self) -> Result<f32, ReadError> {
+        self.try_read_u16().map(f16_bits_to_f32)
+    }
+
+    /// Return an IEEE 754 half precision float, or zero if there are not enough bytes remaining.
+    pub fn read_f16(&mut self) -> f32 {
+        self.try_read_f16().unwrap_or(0.0)
+    }
+
+    /// Return a bfloat16, or a ReadError if there are not enough bytes remaining.
+    pub fn try_read_bf16(&mut self) -> Result<f32, ReadError> {
+        self.try_read_u16().map(bf16_bits_to_f32)
+    }
+
+    /// Return a bfloat16, or zero if there are not enough bytes remaining.
+    pub fn read_bf16(&mut self) -> f32 {
+        self.try_read_bf16().unwrap_or(0.0)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16() {
+        assert_eq!(0x3c00, f32_to_f16_bits(1.0));
+        assert_eq!(0x8000, f32_to_f16_bits(-0.0));
+        assert_eq!(0x7bff, f32_to_f16_bits(65504.0));
+        // 65520 is halfway between 65504 and the next power of two, so rounds to even, which is infinity
+        assert_eq!(0x7bff, f32_to_f16_bits(65519.0));
+        assert_eq!(0x7c00, f32_to_f16_bits(65520.0));
+        assert_eq!(0xfc00, f32_to_f16_bits(-1.0e10));
+        assert_eq!(0x7e00, f32_to_f16_bits(f32::NAN) & 0x7e00);
+        // 1 + 2^-11 is halfway between 1 and 1 + 2^-10, so rounds to even
+        assert_eq!(0x3c00, f32_to_f16_bits(1.0 + 1.0 / 2048.0));
+        assert_eq!(0x3c02, f32_to_f16_bits(1.0 + 3.0 / 2048.0));
+        // subnormals
+        assert_eq!(0x0001, f32_to_f16_bits(F16_SUBNORMAL_UNIT));
+        assert_eq!(0x0000, f32_to_f16_bits(F16_SUBNORMAL_UNIT / 2.0));
+        assert_eq!(0x0001, f32_to_f16_bits(F16_SUBNORMAL_UNIT * 0.75));
+        assert_eq!(0x03ff, f32_to_f16_bits(1023.0 * F16_SUBNORMAL_UNIT));
+        assert_eq!(0x0400, f32_to_f16_bits(1023.75 * F16_SUBNORMAL_UNIT));
+
+        // every f16 survives a round trip through f32
+        for bits in 0..=u16::MAX {
+            let value = f16_bits_to_f32(bits);
+            if value.is_nan() {
+                assert_eq!(F16_INFINITY, bits & F16_INFINITY);
+            } else {
+                assert_eq!(bits, f32_to_f16_bits(value));
+            }
+        }
+    }
+
+    #[test]
+    fn bf16() {
+        assert_eq!(0x3f80, f32_to_bf16_bits(1.0));
+        assert_eq!(0x3f80, f32_to_bf16_bits(f32::from_bits(0x3f80_8000)));
+        assert_eq!(0x3f82, f32_to_bf16_bits(f32::from_bits(0x3f81_8000)));
+        assert_eq!(0x7f80, f32_to_bf16_bits(f32::MAX));
+        assert_eq!(0xff80, f32_to_bf16_bits(f32::NEG_INFINITY));
+        assert!(bf16_bits_to_f32(f32_to_bf16_bits(f32::from_bits(0x7f80_0001))).is_nan());
+    }
+
+    #[test]
+    fn write_read() -> Result<(), ReadError> {
+        let mut data = [0u8; 6];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_f16(0.1);
+        sbuf.write_bf16(-0.1);
+        sbuf.write_f16(1.0e-7);
+        assert_eq!([0x66, 0x2e, 0xcd, 0xbd, 0x02, 0x00], sbuf.get_ref());
+        assert!(sbuf.try_write_bf16(0.0).is_err());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(0.099975586, sbuf_reader.try_read_f16()?);
+        assert_eq!(-0.100097656, sbuf_reader.try_read_bf16()?);
+        assert_eq!(2.0 * F16_SUBNORMAL_UNIT, sbuf_reader.try_read_f16()?);
+        assert!(sbuf_reader.try_read_f16().is_err());
+        assert_eq!(0.0, sbuf_reader.read_bf16());
+        Ok(())
+    }
+}
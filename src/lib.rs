@@ -4,8 +4,50 @@
 #![deny(clippy::panic)]
 #![deny(unused_must_use)]
 
+mod bit_reader;
+mod bit_writer;
+mod blackbox;
+mod byte_order;
+mod byte_stuffing;
+mod checksum;
+mod cobs;
+mod crc;
+mod crsf;
+mod encode;
+mod fixed_point;
+mod half_float;
+mod msp;
+mod msp_dispatch;
+mod placeholder;
+mod sbus;
 mod stream_buf_reader;
 mod stream_buf_writer;
+mod transaction;
+mod varint;
+#[cfg(feature = "vqm")]
+mod vqm;
 
-pub use stream_buf_reader::StreamBufReader;
-pub use stream_buf_writer::StreamBufWriter;
+pub use bit_reader::BitReader;
+pub use bit_writer::{BitOrder, BitWriter};
+pub use byte_order::{BigEndian, ByteOrder, DynamicEndian, LittleEndian, NetworkEndian};
+pub use byte_stuffing::{HdlcDecoder, HdlcError, SlipDecoder, SlipError};
+pub use checksum::{Adler32, Checksum, ChecksumSource, Checksummed, Fletcher16, Sum8, Xor8};
+pub use cobs::{CobsDecoder, CobsError, CobsVariant};
+pub use crc::{CRC_8_DVB_S2, CRC_16_CCITT, CRC_16_MODBUS, CRC_16_X25, CRC_32, Crc, CrcDigest};
+pub use crsf::{
+    CRSF_ADDRESS_BROADCAST, CRSF_ADDRESS_FLIGHT_CONTROLLER, CRSF_ADDRESS_RADIO_TRANSMITTER, CRSF_ADDRESS_RECEIVER,
+    CRSF_ADDRESS_TRANSMITTER, CRSF_SYNC_BYTE, CrsfAttitude, CrsfBattery, CrsfExtendedHeader, CrsfFrame, CrsfGps,
+    CrsfLinkStatistics, CrsfPayload, CrsfRcChannels,
+};
+pub use encode::{Decode, Encode};
+#[doc(hidden)]
+pub use fixed_point::round_scaled;
+pub use msp::{MspDecoder, MspDirection, MspError, MspFrame, MspHeader, MspVersion};
+pub use msp_dispatch::{MspCommand, MspDispatchError, MspDispatcher, MspHandler};
+pub use placeholder::{LengthPrefix, Placeholder};
+pub use sbus::{SBUS_FRAME_SIZE, SbusFrame};
+#[cfg(feature = "derive")]
+pub use stream_buf_derive::{StreamDecode, StreamEncode};
+pub use stream_buf_reader::{ReadError, StreamBufReader};
+pub use stream_buf_writer::{Checkpoint, StreamBufWriter, WriteError};
+pub use transaction::Transaction;
@@ -0,0 +1,643 @@
+#![allow(unused)]
+
+//! MultiWii Serial Protocol (MSP) framing, versions 1 and 2.
+//!
+//! * MSP v1: `$M`, direction, size u8, command u8, payload, XOR checksum of size, command and payload.
+//!   Payloads of 255 bytes or more are sent as jumbo frames: a size of 255 followed, after the command, by the real
+//!   size as a little endian u16, which is included in the checksum.
+//! * MSP v2: `$X`, direction, flags u8, command u16, size u16, payload, CRC-8/DVB-S2 of flags, command, size and
+//!   payload. Multi-byte header fields are little endian.
+//!
+//! The direction is `<` for a request, `>` for a response and `!` for an error response.
+//! ```
+//! # use stream_buf::{MspDecoder, MspDirection, MspHeader, StreamBufWriter};
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! let header = MspHeader::v2(MspDirection::Request, 0, 0x0064);
+//! sbuf_writer.try_write_msp(header, &[]).unwrap();
+//! assert_eq!([b'$', b'X', b'<', 0x00, 0x64, 0x00, 0x00, 0x00, 0x8f], sbuf_writer.get_ref());
+//!
+//! let mut payload = [0u8; 64];
+//! let mut decoder = MspDecoder::new(&mut payload);
+//! let mut frames = 0;
+//! for byte in sbuf_writer.get_ref() {
+//!     if let Ok(Some(frame)) = decoder.decode(*byte) {
+//!         assert_eq!(header, frame.header);
+//!         assert!(frame.payload.is_full());
+//!         frames += 1;
+//!     }
+//! }
+//! assert_eq!(1, frames);
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::checksum::{Checksum, Xor8};
+use crate::crc::{CRC_8_DVB_S2, CrcDigest};
+use crate::stream_buf_reader::StreamBufReader;
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::fmt;
+
+/// Size byte of an MSP v1 frame whose real size follows the command as a u16.
+const MSP_V1_JUMBO_SIZE: u8 = 0xff;
+
+/// MSP protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MspVersion {
+    /// MSP v1, with `$M` preamble, 8-bit command and XOR checksum.
+    V1,
+    /// MSP v2, with `$X` preamble, 16-bit command and CRC-8/DVB-S2.
+    V2,
+}
+
+impl MspVersion {
+    /// Return the second byte of the preamble: `M` for v1, `X` for v2.
+    pub fn preamble(self) -> u8 {
+        match self {
+            Self::V1 => b'M',
+            Self::V2 => b'X',
+        }
+    }
+
+    fn from_preamble(byte: u8) -> Option<Self> {
+        match byte {
+            b'M' => Some(Self::V1),
+            b'X' => Some(Self::V2),
+            _ => None,
+        }
+    }
+}
+
+/// Direction of an MSP frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MspDirection {
+    /// `<`, from the ground station to the flight controller.
+    Request,
+    /// `>`, from the flight controller to the ground station.
+    Response,
+    /// `!`, a response reporting that the request could not be handled.
+    Error,
+}
+
+impl MspDirection {
+    /// Return the direction byte of the frame header.
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Self::Request => b'<',
+            Self::Response => b'>',
+            Self::Error => b'!',
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b'<' => Some(Self::Request),
+            b'>' => Some(Self::Response),
+            b'!' => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Version, direction, flags and command of an MSP frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MspHeader {
+    version: MspVersion,
+    direction: MspDirection,
+    flags: u8,
+    command: u16,
+}
+
+impl MspHeader {
+    /// Create an MSP v1 header.
+    pub fn v1(direction: MspDirection, command: u8) -> Self {
+        Self {
+            version: MspVersion::V1,
+            direction,
+            flags: 0,
+            command: command.into(),
+        }
+    }
+
+    /// Create an MSP v2 header.
+    pub fn v2(direction: MspDirection, flags: u8, command: u16) -> Self {
+        Self {
+            version: MspVersion::V2,
+            direction,
+            flags,
+            command,
+        }
+    }
+
+    /// Return a copy of this header with its direction changed, typically to reply to a request.
+    pub fn with_direction(self, direction: MspDirection) -> Self {
+        Self { direction, ..self }
+    }
+
+    pub fn version(&self) -> MspVersion {
+        self.version
+    }
+
+    pub fn direction(&self) -> MspDirection {
+        self.direction
+    }
+
+    /// Return the flags byte. This is always zero for MSP v1.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn command(&self) -> u16 {
+        self.command
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write an MSP frame with the given `payload`, or return a WriteError if there is not enough space available
+    /// or the payload is longer than 65535 bytes. On error nothing is written.
+    /// ```
+    /// # use stream_buf::{MspDirection, MspHeader, StreamBufWriter};
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// // MSP_SET_RAW_RC request with two channels
+    /// let header = MspHeader::v1(MspDirection::Request, 200);
+    /// sbuf_writer.try_write_msp(header, &[0xdc, 0x05, 0xe8, 0x03]).unwrap();
+    ///
+    /// assert_eq!([b'$', b'M', b'<', 0x04, 0xc8, 0xdc, 0x05, 0xe8, 0x03, 0xfe], sbuf_writer.get_ref());
+    /// ```
+    pub fn try_write_msp(&mut self, header: MspHeader, payload: &[u8]) -> Result<(), WriteError> {
+        self.try_write_msp_with(header, |w| w.try_write(payload))
+    }
+
+    /// Write an MSP frame whose payload is written by `f`, then fill in the size and checksum.
+    /// A v1 frame whose payload turns out to be 255 bytes or more is written as a jumbo frame.
+    ///
+    /// If `f` returns an error, a write within `f` is dropped, or the payload is longer than 65535 bytes, then the
+    /// partial frame is rolled back and an error is returned. A payload that is too long is reported as a WriteError
+    /// with `available` set to 65535.
+    pub fn try_write_msp_with<F>(&mut self, header: MspHeader, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let result = self.write_msp_frame(header, f);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn write_msp_frame<F>(&mut self, header: MspHeader, f: F) -> Result<(), WriteError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        self.try_write(&[b'$', header.version.preamble(), header.direction.as_byte()])?;
+        let start = self.pos();
+        match header.version {
+            MspVersion::V1 => self.try_write(&[0, header.command as u8])?,
+            MspVersion::V2 => {
+                self.try_write_u8(header.flags)?;
+                self.try_write_u16_little_endian(header.command)?;
+                self.try_write_u16_little_endian(0)?;
+            }
+        }
+        let payload_start = self.pos();
+        f(self)?;
+        self.check_no_drops_since(&checkpoint)?;
+        let len = self.pos() - payload_start;
+        let size = u16::try_from(len).map_err(|_| WriteError {
+            pos: payload_start,
+            requested: len,
+            available: u16::MAX.into(),
+        })?;
+
+        let checksum = match header.version {
+            MspVersion::V1 => {
+                if len < MSP_V1_JUMBO_SIZE.into() {
+                    self.try_write_u8_at(start, size as u8)?;
+                } else {
+                    self.try_write_u8_at(start, MSP_V1_JUMBO_SIZE)?;
+                    self.insert_msp_jumbo_size(payload_start, size)?;
+                }
+                let mut xor = Xor8::default();
+                xor.update(&self.get_ref()[start..]);
+                xor.value()
+            }
+            MspVersion::V2 => {
+                self.put_at(start + 3, &size.to_le_bytes())?;
+                self.crc_since(&CRC_8_DVB_S2, start) as u8
+            }
+        };
+        self.try_write_u8(checksum)
+    }
+
+    /// Move the payload starting at `payload_start` up by two bytes and write the jumbo frame size in front of it.
+    fn insert_msp_jumbo_size(&mut self, payload_start: usize, size: u16) -> Result<(), WriteError> {
+        self.try_write(&[0, 0])?;
+        for index in (payload_start..self.pos() - 2).rev() {
+            self[index + 2] = self[index];
+        }
+        self.put_at(payload_start, &size.to_le_bytes())
+    }
+}
+
+/// Error returned by `MspDecoder::decode()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MspError {
+    /// The checksum received does not match the checksum of the frame.
+    Checksum {
+        /// Checksum calculated from the frame.
+        expected: u8,
+        /// Checksum received.
+        received: u8,
+    },
+    /// The payload size in the frame header is larger than the decoder's buffer.
+    Oversize {
+        /// Payload size in the frame header.
+        size: usize,
+        /// Size of the decoder's buffer.
+        capacity: usize,
+    },
+}
+
+impl fmt::Display for MspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Checksum { expected, received } => {
+                write!(f, "checksum {received:#04x} received but {expected:#04x} expected")
+            }
+            Self::Oversize { size, capacity } => {
+                write!(f, "payload of {size} bytes but buffer of only {capacity} bytes")
+            }
+        }
+    }
+}
+
+impl core::error::Error for MspError {}
+
+/// A complete MSP frame, returned by `MspDecoder::decode()`.
+#[derive(Clone)]
+pub struct MspFrame<'a> {
+    pub header: MspHeader,
+    /// Reader over the payload, positioned at its start.
+    pub payload: StreamBufReader<'a>,
+}
+
+/// Next field expected by an `MspDecoder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MspState {
+    Idle,
+    Preamble,
+    Direction,
+    V1Size,
+    V1Command,
+    V1JumboSizeLow,
+    V1JumboSizeHigh,
+    V2Flags,
+    V2CommandLow,
+    V2CommandHigh,
+    V2SizeLow,
+    V2SizeHigh,
+    Payload,
+    Checksum,
+}
+
+/// Decoder for MSP v1 and v2 frames, fed one byte at a time, for example from a UART receive interrupt.
+///
+/// The payload is copied into a buffer supplied by the caller, which limits the size of frame that can be received.
+/// Bytes that are not part of a frame are skipped, so the decoder resynchronizes on the next `$`.
+/// ```
+/// # use stream_buf::{MspDecoder, MspDirection, MspError};
+/// // MSP_ATTITUDE response with a corrupted checksum, then a valid one
+/// let bytes = [
+///     b'$', b'M', b'>', 0x06, 0x6c, 0x0a, 0x00, 0xf6, 0xff, 0x5a, 0x00, 0x00,
+///     b'$', b'M', b'>', 0x06, 0x6c, 0x0a, 0x00, 0xf6, 0xff, 0x5a, 0x00, 0x33,
+/// ];
+/// let mut payload = [0u8; 16];
+/// let mut decoder = MspDecoder::new(&mut payload);
+///
+/// assert!(bytes[..11].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+/// assert_eq!(Some(MspError::Checksum { expected: 0x33, received: 0x00 }), decoder.decode(bytes[11]).err());
+/// assert!(bytes[12..23].iter().all(|byte| matches!(decoder.decode(*byte), Ok(None))));
+///
+/// let mut frame = decoder.decode(bytes[23]).unwrap().unwrap();
+/// assert_eq!((108, MspDirection::Response), (frame.header.command(), frame.header.direction()));
+/// assert_eq!([10, -10, 90], [frame.payload.read_i16(), frame.payload.read_i16(), frame.payload.read_i16()]);
+/// ```
+pub struct MspDecoder<'b> {
+    buf: &'b mut [u8],
+    state: MspState,
+    header: MspHeader,
+    size: usize,
+    len: usize,
+    xor: Xor8,
+    crc: CrcDigest<'static>,
+}
+
+impl<'b> MspDecoder<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            buf,
+            state: MspState::Idle,
+            header: MspHeader::v1(MspDirection::Request, 0),
+            size: 0,
+            len: 0,
+            xor: Xor8::default(),
+            crc: CRC_8_DVB_S2.digest(),
+        }
+    }
+
+    /// Return the size of the largest payload that can be received.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.state = MspState::Idle;
+    }
+
+    /// Add `byte` to the checksum of the frame.
+    fn add(&mut self, byte: u8) {
+        self.xor.update(&[byte]);
+        self.crc.update_u8(byte);
+    }
+
+    /// Record the payload size and return the next state, or an error if the payload does not fit in the buffer.
+    fn start_payload(&mut self, size: usize) -> Result<MspState, MspError> {
+        if size > self.buf.len() {
+            return Err(MspError::Oversize {
+                size,
+                capacity: self.buf.len(),
+            });
+        }
+        self.size = size;
+        self.len = 0;
+        Ok(if size == 0 {
+            MspState::Checksum
+        } else {
+            MspState::Payload
+        })
+    }
+
+    /// Process the next received byte.
+    ///
+    /// Returns the frame when its final byte is received, or an MspError if the frame cannot be received correctly.
+    /// After a frame or an error the decoder waits for the start of the next frame.
+    pub fn decode(&mut self, byte: u8) -> Result<Option<MspFrame<'_>>, MspError> {
+        let state = self.state;
+        self.state = MspState::Idle;
+        self.state = match state {
+            MspState::Idle if byte == b'$' => MspState::Preamble,
+            MspState::Idle => MspState::Idle,
+            MspState::Preamble => match MspVersion::from_preamble(byte) {
+                Some(version) => {
+                    self.header.version = version;
+                    MspState::Direction
+                }
+                None if byte == b'$' => MspState::Preamble,
+                None => MspState::Idle,
+            },
+            MspState::Direction => match MspDirection::from_byte(byte) {
+                Some(direction) => {
+                    self.header.direction = direction;
+                    self.header.flags = 0;
+                    self.xor = Xor8::default();
+                    self.crc = CRC_8_DVB_S2.digest();
+                    match self.header.version {
+                        MspVersion::V1 => MspState::V1Size,
+                        MspVersion::V2 => MspState::V2Flags,
+                    }
+                }
+                None => MspState::Idle,
+            },
+            MspState::V1Size => {
+                self.add(byte);
+                self.size = byte.into();
+                MspState::V1Command
+            }
+            MspState::V1Command => {
+                self.add(byte);
+                self.header.command = byte.into();
+                if self.size == MSP_V1_JUMBO_SIZE.into() {
+                    MspState::V1JumboSizeLow
+                } else {
+                    self.start_payload(self.size)?
+                }
+            }
+            MspState::V1JumboSizeLow | MspState::V2SizeLow => {
+                self.add(byte);
+                self.size = byte.into();
+                match state {
+                    MspState::V1JumboSizeLow => MspState::V1JumboSizeHigh,
+                    _ => MspState::V2SizeHigh,
+                }
+            }
+            MspState::V1JumboSizeHigh | MspState::V2SizeHigh => {
+                self.add(byte);
+                self.start_payload(self.size | (usize::from(byte) << 8))?
+            }
+            MspState::V2Flags => {
+                self.add(byte);
+                self.header.flags = byte;
+                MspState::V2CommandLow
+            }
+            MspState::V2CommandLow => {
+                self.add(byte);
+                self.header.command = byte.into();
+                MspState::V2CommandHigh
+            }
+            MspState::V2CommandHigh => {
+                self.add(byte);
+                self.header.command |= u16::from(byte) << 8;
+                MspState::V2SizeLow
+            }
+            MspState::Payload => {
+                self.add(byte);
+                self.buf[self.len] = byte;
+                self.len += 1;
+                if self.len == self.size {
+                    MspState::Checksum
+                } else {
+                    MspState::Payload
+                }
+            }
+            MspState::Checksum => {
+                let expected = match self.header.version {
+                    MspVersion::V1 => self.xor.value(),
+                    MspVersion::V2 => self.crc.finalize() as u8,
+                };
+                if byte != expected {
+                    return Err(MspError::Checksum {
+                        expected,
+                        received: byte,
+                    });
+                }
+                return Ok(Some(MspFrame {
+                    header: self.header,
+                    payload: StreamBufReader::new(&self.buf[..self.size]),
+                }));
+            }
+        };
+        Ok(None)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    /// Feed `bytes` to `decoder`, returning the header and payload of the last frame decoded and the last error.
+    fn decode_all(
+        decoder: &mut MspDecoder<'_>,
+        bytes: &[u8],
+        payload: &mut [u8],
+    ) -> (Option<MspHeader>, Option<MspError>) {
+        let mut header = None;
+        let mut error = None;
+        for byte in bytes {
+            match decoder.decode(*byte) {
+                Ok(Some(frame)) => {
+                    let len = frame.payload.bytes_remaining();
+                    payload[..len].copy_from_slice(frame.payload.remaining_slice());
+                    header = Some(frame.header);
+                }
+                Ok(None) => {}
+                Err(e) => error = Some(e),
+            }
+        }
+        (header, error)
+    }
+
+    #[test]
+    fn msp_v1() {
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        // MSP_API_VERSION request
+        let request = MspHeader::v1(MspDirection::Request, 1);
+        assert_eq!(Ok(()), sbuf.try_write_msp(request, &[]));
+        assert_eq!([b'$', b'M', b'<', 0x00, 0x01, 0x01], sbuf.get_ref());
+        let response = request.with_direction(MspDirection::Response);
+        assert_eq!(
+            Ok(()),
+            sbuf.try_write_msp_with(response, |w| w.try_write(&[0x00, 0x01, 0x2e]))
+        );
+        assert_eq!(
+            [b'$', b'M', b'>', 0x03, 0x01, 0x00, 0x01, 0x2e, 0x2d],
+            sbuf.get_ref()[6..]
+        );
+        let error = request.with_direction(MspDirection::Error);
+        assert_eq!(Ok(()), sbuf.try_write_msp(error, &[]));
+        assert_eq!([b'$', b'M', b'!', 0x00, 0x01, 0x01], sbuf.get_ref()[15..]);
+        assert_eq!(21, sbuf.bytes_written());
+
+        let mut buf = [0u8; 8];
+        let mut decoder = MspDecoder::new(&mut buf);
+        let mut payload = [0u8; 8];
+        assert_eq!(
+            (Some(response), None),
+            decode_all(&mut decoder, &sbuf.get_ref()[..15], &mut payload)
+        );
+        assert_eq!([0x00, 0x01, 0x2e], payload[..3]);
+        assert_eq!(
+            (Some(error), None),
+            decode_all(&mut decoder, &sbuf.get_ref()[15..], &mut payload)
+        );
+    }
+
+    #[test]
+    fn msp_v1_jumbo() {
+        let mut data = [0u8; 320];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        let header = MspHeader::v1(MspDirection::Response, 116);
+        let mut expected_checksum = 0xff ^ 116 ^ 0x2c ^ 0x01;
+        assert_eq!(
+            Ok(()),
+            sbuf.try_write_msp_with(header, |w| {
+                for index in 0..300u16 {
+                    w.try_write_u8(index as u8)?;
+                    expected_checksum ^= index as u8;
+                }
+                Ok(())
+            })
+        );
+        assert_eq!(308, sbuf.bytes_written());
+        assert_eq!(
+            [b'$', b'M', b'>', 0xff, 116, 0x2c, 0x01, 0x00, 0x01],
+            sbuf.get_ref()[..9]
+        );
+        assert_eq!([0x2a, 0x2b, expected_checksum], sbuf.get_ref()[305..]);
+
+        let mut buf = [0u8; 300];
+        let mut decoder = MspDecoder::new(&mut buf);
+        let mut payload = [0u8; 300];
+        assert_eq!(
+            (Some(header), None),
+            decode_all(&mut decoder, sbuf.get_ref(), &mut payload)
+        );
+        assert_eq!(&sbuf.get_ref()[7..307], payload);
+
+        let mut buf = [0u8; 299];
+        let mut decoder = MspDecoder::new(&mut buf);
+        assert_eq!(
+            (
+                None,
+                Some(MspError::Oversize {
+                    size: 300,
+                    capacity: 299
+                })
+            ),
+            decode_all(&mut decoder, sbuf.get_ref(), &mut payload)
+        );
+    }
+
+    #[test]
+    fn msp_v2() {
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        let header = MspHeader::v2(MspDirection::Request, 0x01, 0x1f03);
+        assert_eq!(Ok(()), sbuf.try_write_msp(header, &[0x0a, 0x1b, 0x2c]));
+        let crc = CRC_8_DVB_S2.checksum(&[0x01, 0x03, 0x1f, 0x03, 0x00, 0x0a, 0x1b, 0x2c]) as u8;
+        assert_eq!(
+            [b'$', b'X', b'<', 0x01, 0x03, 0x1f, 0x03, 0x00, 0x0a, 0x1b, 0x2c, crc],
+            sbuf.get_ref()
+        );
+
+        // a frame that does not fit is rolled back
+        assert!(sbuf.try_write_msp(header, &[0; 12]).is_err());
+        assert!(!sbuf.overflowed());
+        assert!(
+            sbuf.try_write_msp_with(header, |w| {
+                w.write_u32(0);
+                w.write_u64(0);
+                w.write_u64(0);
+                Ok(())
+            })
+            .is_err()
+        );
+        assert!(!sbuf.overflowed());
+        assert_eq!(12, sbuf.bytes_written());
+
+        // garbage before the frame and a corrupted frame are skipped
+        let mut bytes = [0u8; 30];
+        bytes[..6].copy_from_slice(b"$M$X?$");
+        bytes[6..18].copy_from_slice(sbuf.get_ref());
+        bytes[18..30].copy_from_slice(sbuf.get_ref());
+        bytes[15] ^= 0x01;
+        let mut buf = [0u8; 8];
+        let mut decoder = MspDecoder::new(&mut buf);
+        let mut payload = [0u8; 8];
+        assert_eq!(
+            (
+                Some(header),
+                Some(MspError::Checksum {
+                    expected: CRC_8_DVB_S2.checksum(&[0x01, 0x03, 0x1f, 0x03, 0x00, 0x0a, 0x1a, 0x2c]) as u8,
+                    received: crc
+                })
+            ),
+            decode_all(&mut decoder, &bytes, &mut payload)
+        );
+        assert_eq!([0x0a, 0x1b, 0x2c], payload[..3]);
+    }
+}
@@ -0,0 +1,308 @@
+#![allow(unused)]
+
+//! Table of MSP command handlers, so that a decoded request is answered by looking up its command rather than by a
+//! match statement over every command the firmware supports.
+//!
+//! Each handler receives a context, typically the firmware state, a reader over the request payload and a writer for
+//! the reply payload. The dispatcher frames the reply, and replies with an MSP error frame if the command is unknown,
+//! the request payload is too short or the reply does not fit.
+//! ```
+//! # use stream_buf::{MspCommand, MspDecoder, MspDirection, MspDispatchError, MspDispatcher, MspHeader};
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! struct FlightController {
+//!     armed: bool,
+//! }
+//!
+//! fn arm(fc: &mut FlightController, request: &mut StreamBufReader<'_>, _reply: &mut StreamBufWriter<'_>)
+//!     -> Result<(), MspDispatchError>
+//! {
+//!     fc.armed = request.try_read_u8()? != 0;
+//!     Ok(())
+//! }
+//!
+//! fn status(fc: &mut FlightController, _request: &mut StreamBufReader<'_>, reply: &mut StreamBufWriter<'_>)
+//!     -> Result<(), MspDispatchError>
+//! {
+//!     reply.try_write_u8(fc.armed.into())?;
+//!     Ok(())
+//! }
+//!
+//! static COMMANDS: [MspCommand<FlightController>; 2] = [MspCommand::new(101, status), MspCommand::new(200, arm)];
+//! let dispatcher = MspDispatcher::new(&COMMANDS);
+//! let mut fc = FlightController { armed: false };
+//!
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.try_write_msp(MspHeader::v1(MspDirection::Request, 200), &[1]).unwrap();
+//!
+//! let mut payload = [0u8; 16];
+//! let mut decoder = MspDecoder::new(&mut payload);
+//! let mut reply_data = [0u8; 16];
+//! let mut reply = StreamBufWriter::new(&mut reply_data);
+//! for byte in sbuf_writer.get_ref() {
+//!     if let Ok(Some(frame)) = decoder.decode(*byte) {
+//!         dispatcher.dispatch(&mut fc, frame, &mut reply).unwrap();
+//!     }
+//! }
+//!
+//! assert!(fc.armed);
+//! assert_eq!([b'$', b'M', b'>', 0x00, 0xc8, 0xc8], reply.get_ref());
+//! ```
+
+use crate::msp::{MspDirection, MspFrame};
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::fmt;
+
+/// Error returned by an MSP handler and by `MspDispatcher::dispatch()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MspDispatchError {
+    /// There is no handler for the command.
+    UnknownCommand(u16),
+    /// The request payload could not be read, typically because it is too short.
+    Read(ReadError),
+    /// The reply did not fit in the writer.
+    Write(WriteError),
+}
+
+impl fmt::Display for MspDispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(command) => write!(f, "unknown MSP command {command}"),
+            Self::Read(error) => write!(f, "bad request: {error}"),
+            Self::Write(error) => write!(f, "reply too large: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for MspDispatchError {}
+
+impl From<ReadError> for MspDispatchError {
+    fn from(error: ReadError) -> Self {
+        Self::Read(error)
+    }
+}
+
+impl From<WriteError> for MspDispatchError {
+    fn from(error: WriteError) -> Self {
+        Self::Write(error)
+    }
+}
+
+/// Handler for an MSP command. It is given the context, a reader over the request payload and a writer positioned at
+/// the start of the reply payload.
+pub type MspHandler<C> = fn(&mut C, &mut StreamBufReader<'_>, &mut StreamBufWriter<'_>) -> Result<(), MspDispatchError>;
+
+/// Entry in the table of an `MspDispatcher`.
+pub struct MspCommand<C> {
+    pub command: u16,
+    pub handler: MspHandler<C>,
+}
+
+impl<C> MspCommand<C> {
+    pub const fn new(command: u16, handler: MspHandler<C>) -> Self {
+        Self { command, handler }
+    }
+}
+
+/// Dispatcher of MSP requests to the handlers in a table, which is usually a static.
+pub struct MspDispatcher<'h, C> {
+    commands: &'h [MspCommand<C>],
+}
+
+impl<'h, C> MspDispatcher<'h, C> {
+    /// Create a dispatcher for `commands`. If a command appears more than once the first entry is used.
+    pub const fn new(commands: &'h [MspCommand<C>]) -> Self {
+        Self { commands }
+    }
+
+    /// Return the handler for `command`, or None if there is none.
+    pub fn handler(&self, command: u16) -> Option<MspHandler<C>> {
+        self.commands
+            .iter()
+            .find(|entry| entry.command == command)
+            .map(|entry| entry.handler)
+    }
+
+    /// Call the handler for the command of `request` and write the reply frame, in the MSP version of the request,
+    /// to `reply`.
+    ///
+    /// If there is no handler, or the handler returns an error, or a write by the handler is dropped, then anything
+    /// written by the handler is rolled back, an error frame with an empty payload is written instead and the error
+    /// is returned. The error frame is not written if there is no space for it.
+    pub fn dispatch(
+        &self,
+        context: &mut C,
+        request: MspFrame<'_>,
+        reply: &mut StreamBufWriter<'_>,
+    ) -> Result<(), MspDispatchError> {
+        let header = request.header;
+        let result = match self.handler(header.command()) {
+            None => Err(MspDispatchError::UnknownCommand(header.command())),
+            Some(handler) => {
+                let mut payload = request.payload;
+                let mut handler_result = Ok(());
+                let checkpoint = reply.checkpoint();
+                let reply_result = reply.try_write_msp_with(header.with_direction(MspDirection::Response), |w| {
+                    handler_result = handler(context, &mut payload, w);
+                    Ok(())
+                });
+                let result = handler_result.and(reply_result.map_err(MspDispatchError::from));
+                if result.is_err() {
+                    reply.rollback(checkpoint);
+                }
+                result
+            }
+        };
+        if result.is_err() {
+            let _ = reply.try_write_msp(header.with_direction(MspDirection::Error), &[]);
+        }
+        result
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+    use crate::msp::{MspDecoder, MspHeader};
+
+    #[derive(Default)]
+    struct Context {
+        value: u16,
+    }
+
+    fn get_value(
+        context: &mut Context,
+        _request: &mut StreamBufReader<'_>,
+        reply: &mut StreamBufWriter<'_>,
+    ) -> Result<(), MspDispatchError> {
+        reply.try_write_u16(context.value)?;
+        Ok(())
+    }
+
+    fn set_value(
+        context: &mut Context,
+        request: &mut StreamBufReader<'_>,
+        _reply: &mut StreamBufWriter<'_>,
+    ) -> Result<(), MspDispatchError> {
+        context.value = request.try_read_u16()?;
+        Ok(())
+    }
+
+    fn large(
+        _context: &mut Context,
+        _request: &mut StreamBufReader<'_>,
+        reply: &mut StreamBufWriter<'_>,
+    ) -> Result<(), MspDispatchError> {
+        reply.write_u32(1);
+        reply.write_u32(2);
+        reply.write_u32(3);
+        Ok(())
+    }
+
+    static COMMANDS: [MspCommand<Context>; 3] = [
+        MspCommand::new(1, get_value),
+        MspCommand::new(2, set_value),
+        MspCommand::new(0x1003, large),
+    ];
+
+    /// Encode `payload` as a request for `header`, then decode and dispatch it.
+    fn dispatch(
+        context: &mut Context,
+        header: MspHeader,
+        payload: &[u8],
+        reply: &mut StreamBufWriter<'_>,
+    ) -> Result<(), MspDispatchError> {
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_msp(header, payload));
+        let mut buf = [0u8; 16];
+        let mut decoder = MspDecoder::new(&mut buf);
+        let (last, frame) = sbuf.get_ref().split_last().unwrap_or((&0, &[]));
+        for byte in frame {
+            assert!(matches!(decoder.decode(*byte), Ok(None)));
+        }
+        match decoder.decode(*last) {
+            Ok(Some(frame)) => MspDispatcher::new(&COMMANDS).dispatch(context, frame, reply),
+            _ => Err(MspDispatchError::UnknownCommand(0xffff)),
+        }
+    }
+
+    #[test]
+    fn dispatch_v1() {
+        let mut context = Context::default();
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+
+        let set = MspHeader::v1(MspDirection::Request, 2);
+        assert_eq!(Ok(()), dispatch(&mut context, set, &[0x1b, 0x0a], &mut sbuf));
+        assert_eq!(0x0a1b, context.value);
+        assert_eq!([b'$', b'M', b'>', 0x00, 0x02, 0x02], sbuf.get_ref());
+
+        sbuf.reset();
+        let get = MspHeader::v1(MspDirection::Request, 1);
+        assert_eq!(Ok(()), dispatch(&mut context, get, &[], &mut sbuf));
+        assert_eq!([b'$', b'M', b'>', 0x02, 0x01, 0x1b, 0x0a, 0x12], sbuf.get_ref());
+
+        sbuf.reset();
+        assert_eq!(
+            Err(MspDispatchError::Read(ReadError::Truncated {
+                pos: 0,
+                requested: 2,
+                available: 1
+            })),
+            dispatch(&mut context, set, &[0x2c], &mut sbuf)
+        );
+        assert_eq!(0x0a1b, context.value);
+        assert_eq!([b'$', b'M', b'!', 0x00, 0x02, 0x02], sbuf.get_ref());
+
+        sbuf.reset();
+        let unknown = MspHeader::v1(MspDirection::Request, 3);
+        assert_eq!(
+            Err(MspDispatchError::UnknownCommand(3)),
+            dispatch(&mut context, unknown, &[], &mut sbuf)
+        );
+        assert_eq!([b'$', b'M', b'!', 0x00, 0x03, 0x03], sbuf.get_ref());
+    }
+
+    #[test]
+    fn dispatch_v2_overflow() {
+        let mut context = Context::default();
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+
+        let large = MspHeader::v2(MspDirection::Request, 0, 0x1003);
+        assert_eq!(
+            Err(MspDispatchError::Write(WriteError {
+                pos: 16,
+                requested: 4,
+                available: 0
+            })),
+            dispatch(&mut context, large, &[], &mut sbuf)
+        );
+        assert!(!sbuf.overflowed());
+        assert_eq!([b'$', b'X', b'!', 0x00, 0x03, 0x10, 0x00, 0x00], sbuf.get_ref()[..8]);
+        assert_eq!(9, sbuf.bytes_written());
+
+        // a dropped write is detected even if the reply writer had already overflowed
+        let mut data = [0u8; 18];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.fill(0, 19);
+        assert_eq!(
+            Err(MspDispatchError::Write(WriteError {
+                pos: 16,
+                requested: 4,
+                available: 2
+            })),
+            dispatch(&mut context, large, &[], &mut sbuf)
+        );
+        assert_eq!([b'$', b'X', b'!', 0x00, 0x03, 0x10, 0x00, 0x00], sbuf.get_ref()[..8]);
+        assert_eq!(9, sbuf.bytes_written());
+
+        // no space for the error frame either
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert!(dispatch(&mut context, large, &[], &mut sbuf).is_err());
+        assert_eq!(0, sbuf.bytes_written());
+    }
+}
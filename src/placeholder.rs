@@ -0,0 +1,304 @@
+#![allow(unused)]
+
+//! Reserving fields, such as lengths and checksums, that are written before the data they describe and filled in
+//! once that data has been written.
+
+use crate::byte_order::ByteOrder;
+use crate::encode::Encode;
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::marker::PhantomData;
+
+/// A field reserved in a StreamBufWriter, to be filled in later with `fill_placeholder()`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Placeholder<T> {
+    pos: usize,
+    len: usize,
+    value: PhantomData<T>,
+}
+
+impl<T> Clone for Placeholder<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Placeholder<T> {}
+
+impl<T> Placeholder<T> {
+    /// Return the position of the reserved field in the stream_buf.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Return the size of the reserved field in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Unsigned integer type that can be used as the length field of `StreamBufWriter::length_prefixed()`.
+pub trait LengthPrefix: Encode + Default + TryFrom<usize> {
+    /// Largest length that can be represented.
+    const MAX: usize;
+}
+
+impl LengthPrefix for u8 {
+    const MAX: usize = u8::MAX as usize;
+}
+
+impl LengthPrefix for u16 {
+    const MAX: usize = u16::MAX as usize;
+}
+
+impl LengthPrefix for u32 {
+    const MAX: usize = if usize::BITS < u32::BITS {
+        usize::MAX
+    } else {
+        u32::MAX as usize
+    };
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Reserve space for a value of type T, written as `T::default()`, to be filled in later with `fill_placeholder()`.
+    pub fn reserve<T: Encode + Default>(&mut self) -> Result<Placeholder<T>, WriteError> {
+        let pos = self.pos();
+        self.put(&T::default())?;
+        Ok(Placeholder {
+            pos,
+            len: self.pos() - pos,
+            value: PhantomData,
+        })
+    }
+
+    /// Reserve a u8, to be filled in later with `fill_placeholder()`.
+    pub fn reserve_u8(&mut self) -> Result<Placeholder<u8>, WriteError> {
+        self.reserve()
+    }
+
+    /// Reserve a u16, to be filled in later with `fill_placeholder()`.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 8];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// let length = sbuf_writer.reserve_u16().unwrap();
+    /// sbuf_writer.write_str("abc");
+    /// sbuf_writer.fill_placeholder(length, 3).unwrap();
+    ///
+    /// assert_eq!([0x03, 0x00, b'a', b'b', b'c'], sbuf_writer.get_ref());
+    /// ```
+    pub fn reserve_u16(&mut self) -> Result<Placeholder<u16>, WriteError> {
+        self.reserve()
+    }
+
+    /// Reserve a u32, to be filled in later with `fill_placeholder()`.
+    pub fn reserve_u32(&mut self) -> Result<Placeholder<u32>, WriteError> {
+        self.reserve()
+    }
+
+    /// Write `value` into a field previously reserved with `reserve()`, without changing the position.
+    /// Returns a WriteError if the encoded value is larger than the reserved field.
+    pub fn fill_placeholder<T: Encode>(&mut self, placeholder: Placeholder<T>, value: T) -> Result<(), WriteError> {
+        self.fill_placeholder_with_byte_order(placeholder, value, self.byte_order())
+    }
+
+    /// Write `value` into a field previously reserved with `reserve()` using `byte_order` rather than the byte order
+    /// of the stream_buf, without changing the position.
+    /// Returns a WriteError if the encoded value is larger than the reserved field.
+    pub fn fill_placeholder_with_byte_order<T: Encode, B: ByteOrder>(
+        &mut self,
+        placeholder: Placeholder<T>,
+        value: T,
+        byte_order: B,
+    ) -> Result<(), WriteError> {
+        self.writer_at_with_byte_order(placeholder.pos, placeholder.len, byte_order)?
+            .put(&value)
+            .map_err(|error| WriteError {
+                pos: placeholder.pos + error.pos,
+                ..error
+            })
+    }
+
+    /// Write a length field of type L, then the payload written by `f`, then fill in the length field with the length
+    /// of the payload. The length field uses the byte order of the stream_buf; use `length_prefixed_with_byte_order()`
+    /// to choose another.
+    ///
+    /// If `f` returns an error, a write within `f` is dropped, or the payload is too long to be represented by L,
+    /// then the length field and the partial payload are rolled back and an error is returned. A payload that is too
+    /// long is reported as a WriteError with `available` set to `L::MAX`.
+    /// ```
+    /// # use stream_buf::{BigEndian, StreamBufWriter};
+    /// let mut data = [0u8; 8];
+    /// let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+    ///
+    /// sbuf_writer.length_prefixed::<u16, _>(|w| w.try_write_u32(0x0a1b2c3d)).unwrap();
+    /// assert_eq!([0x00, 0x04, 0x0a, 0x1b, 0x2c, 0x3d], sbuf_writer.get_ref());
+    ///
+    /// assert!(sbuf_writer.length_prefixed::<u8, _>(|w| w.try_write_u16(0x4e5f)).is_err());
+    /// assert_eq!(6, sbuf_writer.bytes_written());
+    /// ```
+    pub fn length_prefixed<L, F>(&mut self, f: F) -> Result<(), WriteError>
+    where
+        L: LengthPrefix,
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        self.length_prefixed_with_byte_order::<L, E, F>(self.byte_order(), f)
+    }
+
+    /// Write a length-prefixed record as `length_prefixed()`, with the length field in `byte_order`.
+    /// ```
+    /// # use stream_buf::{BigEndian, StreamBufWriter};
+    /// let mut data = [0u8; 8];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.length_prefixed_with_byte_order::<u16, _, _>(BigEndian, |w| w.try_write_u16(0x0a1b)).unwrap();
+    /// assert_eq!([0x00, 0x02, 0x1b, 0x0a], sbuf_writer.get_ref());
+    /// ```
+    pub fn length_prefixed_with_byte_order<L, B, F>(&mut self, byte_order: B, f: F) -> Result<(), WriteError>
+    where
+        L: LengthPrefix,
+        B: ByteOrder,
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let result = self.write_length_prefixed::<L, B, F>(byte_order, f);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn write_length_prefixed<L, B, F>(&mut self, byte_order: B, f: F) -> Result<(), WriteError>
+    where
+        L: LengthPrefix,
+        B: ByteOrder,
+        F: FnOnce(&mut Self) -> Result<(), WriteError>,
+    {
+        let checkpoint = self.checkpoint();
+        let placeholder = self.reserve::<L>()?;
+        let start = self.pos();
+        f(self)?;
+        self.check_no_drops_since(&checkpoint)?;
+        let len = self.pos() - start;
+        let length = L::try_from(len).map_err(|_| WriteError {
+            pos: start,
+            requested: len,
+            available: L::MAX,
+        })?;
+        self.fill_placeholder_with_byte_order(placeholder, length, byte_order)
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+    use crate::byte_order::{BigEndian, LittleEndian};
+    use crate::stream_buf_reader::StreamBufReader;
+
+    #[test]
+    fn placeholder() -> Result<(), WriteError> {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        let command = sbuf.reserve_u8()?;
+        let checksum = sbuf.reserve::<[u8; 2]>()?;
+        let size = sbuf.reserve_u32()?;
+        assert_eq!((0, 1), (command.pos(), command.len()));
+        assert_eq!((1, 2), (checksum.pos(), checksum.len()));
+        assert_eq!((3, 4), (size.pos(), size.len()));
+        sbuf.write_u16(0x1234);
+
+        assert_eq!(Ok(()), sbuf.fill_placeholder(size, 2));
+        assert_eq!(Ok(()), sbuf.fill_placeholder(command, 0x77));
+        assert_eq!(Ok(()), sbuf.fill_placeholder(checksum, [0xaa, 0xbb]));
+        assert_eq!(9, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(
+            Ok((0x77, [0xaa, 0xbb], 2, 0x1234)),
+            sbuf_reader.get::<(u8, [u8; 2], u32, u16)>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Ok(()),
+            sbuf.length_prefixed::<u8, _>(|w| {
+                w.write_u8(1);
+                w.length_prefixed::<u16, _>(|w| w.try_write_u16(2))
+            })
+        );
+        assert_eq!([0x05, 0x01, 0x02, 0x00, 0x02, 0x00], sbuf.get_ref());
+
+        // a dropped write within the payload rolls back the whole record
+        assert_eq!(
+            Err(WriteError {
+                pos: 7,
+                requested: 2,
+                available: 1
+            }),
+            sbuf.length_prefixed::<u8, _>(|w| {
+                w.write_u16(3);
+                Ok(())
+            })
+        );
+        assert_eq!(6, sbuf.bytes_written());
+        assert!(!sbuf.overflowed());
+
+        // including when an earlier write has already set the overflow flag
+        sbuf.write_u32(4);
+        let overflow = sbuf.overflow_error();
+        assert_eq!(
+            Err(WriteError {
+                pos: 8,
+                requested: 2,
+                available: 0
+            }),
+            sbuf.length_prefixed::<u8, _>(|w| {
+                w.write_u8(1);
+                w.write_u16(1);
+                Ok(())
+            })
+        );
+        assert_eq!(6, sbuf.bytes_written());
+        assert_eq!(overflow, sbuf.overflow_error());
+
+        let mut data = [0u8; 300];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Err(WriteError {
+                pos: 1,
+                requested: 256,
+                available: 255
+            }),
+            sbuf.length_prefixed::<u8, _>(|w| w.try_fill(0, 256))
+        );
+        assert_eq!(0, sbuf.bytes_written());
+    }
+
+    #[test]
+    fn length_prefixed_with_byte_order() {
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Ok(()),
+            sbuf.length_prefixed_with_byte_order::<u32, _, _>(BigEndian, |w| w.try_write_u16(0x0a1b))
+        );
+        assert_eq!([0x00, 0x00, 0x00, 0x02, 0x1b, 0x0a], sbuf.get_ref());
+
+        let mut data = [0u8; 16];
+        let mut sbuf = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+        assert_eq!(
+            Ok(()),
+            sbuf.length_prefixed_with_byte_order::<u16, _, _>(LittleEndian, |w| w.try_write_u16(0x0a1b))
+        );
+        assert_eq!([0x02, 0x00, 0x0a, 0x1b], sbuf.get_ref());
+    }
+}
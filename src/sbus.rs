@@ -0,0 +1,184 @@
+#![allow(unused)]
+
+//! SBUS frames, as output by Futaba, FrSky and many other receivers.
+//!
+//! A frame is 25 bytes: a header byte of 0x0F, 16 channels of 11 bits packed least significant bit first into 22
+//! bytes, a flags byte and a footer byte of 0x00. The flags byte holds the digital channels 17 and 18 and the frame
+//! lost and failsafe flags. SBUS2 receivers use footers of 0x04, 0x14, 0x24 and 0x34, which are also accepted.
+//! ```
+//! # use stream_buf::{SbusFrame, StreamBufReader, StreamBufWriter};
+//! let mut frame = SbusFrame::default();
+//! frame.channels[0] = 992;
+//! frame.failsafe = true;
+//!
+//! let mut data = [0u8; 25];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//! sbuf_writer.put(&frame).unwrap();
+//! assert_eq!([0x0f, 0xe0, 0x03, 0x00], sbuf_writer.get_ref()[..4]);
+//! assert_eq!([0x08, 0x00], sbuf_writer.get_ref()[23..]);
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!(Ok(frame), sbuf_reader.get::<SbusFrame>());
+//! ```
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::{BitOrder, BitWriter};
+use crate::byte_order::ByteOrder;
+use crate::encode::{Decode, Encode};
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+pub const SBUS_FRAME_SIZE: usize = 25;
+const SBUS_HEADER: u8 = 0x0f;
+const SBUS_FOOTER: u8 = 0x00;
+/// Mask and value of the SBUS2 footers 0x04, 0x14, 0x24 and 0x34, which differ only in bits 4 and 5.
+const SBUS2_FOOTER_MASK: u8 = 0xcf;
+const SBUS2_FOOTER: u8 = 0x04;
+/// Number of bits in each packed channel.
+const SBUS_CHANNEL_BITS: u32 = 11;
+
+const SBUS_FLAG_CHANNEL_17: u8 = 0x01;
+const SBUS_FLAG_CHANNEL_18: u8 = 0x02;
+const SBUS_FLAG_FRAME_LOST: u8 = 0x04;
+const SBUS_FLAG_FAILSAFE: u8 = 0x08;
+
+/// Contents of an SBUS frame.
+///
+/// The channel values are as sent, typically 172 to 1811 for 988us to 2012us, and are masked to 11 bits when encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SbusFrame {
+    pub channels: [u16; 16],
+    pub channel_17: bool,
+    pub channel_18: bool,
+    /// Set by the receiver when a frame from the transmitter was missed.
+    pub frame_lost: bool,
+    /// Set by the receiver when the link has been lost and the channels hold the failsafe values.
+    pub failsafe: bool,
+}
+
+impl SbusFrame {
+    fn flags(&self) -> u8 {
+        [
+            (self.channel_17, SBUS_FLAG_CHANNEL_17),
+            (self.channel_18, SBUS_FLAG_CHANNEL_18),
+            (self.frame_lost, SBUS_FLAG_FRAME_LOST),
+            (self.failsafe, SBUS_FLAG_FAILSAFE),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag)
+    }
+}
+
+impl Encode for SbusFrame {
+    fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+        writer.check_available(SBUS_FRAME_SIZE)?;
+        writer.try_write_u8(SBUS_HEADER)?;
+        let mut bit_writer = BitWriter::new(writer, BitOrder::LsbFirst);
+        for channel in self.channels {
+            bit_writer.try_write_bits(channel.into(), SBUS_CHANNEL_BITS)?;
+        }
+        drop(bit_writer);
+        writer.try_write(&[self.flags(), SBUS_FOOTER])
+    }
+}
+
+/// Returns `ReadError::Invalid`, with the position of the offending byte, if the header or footer is not valid.
+impl Decode for SbusFrame {
+    fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+        reader.check_remaining(SBUS_FRAME_SIZE)?;
+        let pos = reader.pos();
+        if reader.try_read_u8()? != SBUS_HEADER {
+            return Err(ReadError::Invalid { pos });
+        }
+        let mut channels = [0u16; 16];
+        let mut bit_reader = BitReader::new(reader, BitOrder::LsbFirst);
+        for channel in &mut channels {
+            *channel = bit_reader.try_read_bits(SBUS_CHANNEL_BITS)? as u16;
+        }
+        let flags = reader.try_read_u8()?;
+        let footer = reader.try_read_u8()?;
+        if footer != SBUS_FOOTER && footer & SBUS2_FOOTER_MASK != SBUS2_FOOTER {
+            return Err(ReadError::Invalid {
+                pos: pos + SBUS_FRAME_SIZE - 1,
+            });
+        }
+        Ok(Self {
+            channels,
+            channel_17: flags & SBUS_FLAG_CHANNEL_17 != 0,
+            channel_18: flags & SBUS_FLAG_CHANNEL_18 != 0,
+            frame_lost: flags & SBUS_FLAG_FRAME_LOST != 0,
+            failsafe: flags & SBUS_FLAG_FAILSAFE != 0,
+        })
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sbus() {
+        let mut frame = SbusFrame {
+            channel_17: true,
+            frame_lost: true,
+            ..Default::default()
+        };
+        for (index, channel) in frame.channels.iter_mut().enumerate() {
+            *channel = 0x7ff - index as u16;
+        }
+        let mut data = [0u8; 2 * SBUS_FRAME_SIZE];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.put(&frame));
+        assert_eq!([0x0f, 0xff, 0xf7, 0x7f], sbuf.get_ref()[..4]);
+        assert_eq!([0x05, 0x00], sbuf.get_ref()[23..25]);
+
+        // a frame that does not fit is not written at all
+        sbuf.write(&[0; 24]);
+        assert_eq!(
+            Err(WriteError {
+                pos: 49,
+                requested: 25,
+                available: 1
+            }),
+            sbuf.put(&frame)
+        );
+        assert_eq!(49, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(Ok(frame), sbuf_reader.get::<SbusFrame>());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 25,
+                requested: 25,
+                available: 24
+            }),
+            sbuf_reader.get::<SbusFrame>()
+        );
+        assert_eq!(25, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn sbus_footer() {
+        let mut buf = [0u8; SBUS_FRAME_SIZE];
+        buf[0] = SBUS_HEADER;
+        buf[23] = SBUS_FLAG_CHANNEL_18 | SBUS_FLAG_FAILSAFE;
+        for footer in [0x04, 0x14, 0x24, 0x34] {
+            buf[24] = footer;
+            let mut sbuf_reader = StreamBufReader::new(&buf);
+            let frame = sbuf_reader
+                .get::<SbusFrame>()
+                .map(|frame| (frame.channel_17, frame.channel_18, frame.frame_lost, frame.failsafe));
+            assert_eq!(Ok((false, true, false, true)), frame);
+        }
+        for footer in [0x08, 0x44, 0xf4] {
+            buf[24] = footer;
+            let mut sbuf_reader = StreamBufReader::new(&buf);
+            assert_eq!(Err(ReadError::Invalid { pos: 24 }), sbuf_reader.get::<SbusFrame>());
+        }
+        buf[0] = 0x0e;
+        buf[24] = 0x00;
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Err(ReadError::Invalid { pos: 0 }), sbuf_reader.get::<SbusFrame>());
+    }
+}
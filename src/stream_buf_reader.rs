@@ -1,12 +1,119 @@
 #![allow(unused)]
 
+use crate::byte_order::{ByteOrder, LittleEndian};
+use crate::encode::Decode;
+use core::fmt;
 use core::mem;
 use core::ops::Index;
 
+/// Error returned by the `try_read_*` functions and by `Decode` implementations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    /// There are not enough bytes remaining in the stream_buf.
+    Truncated {
+        /// Position in the stream_buf at which the read was attempted.
+        pos: usize,
+        /// Number of bytes requested.
+        requested: usize,
+        /// Number of bytes actually available.
+        available: usize,
+    },
+    /// The bytes read do not represent a valid value, for example an unknown enum discriminant.
+    Invalid {
+        /// Position in the stream_buf of the start of the invalid value.
+        pos: usize,
+    },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated {
+                pos,
+                requested,
+                available,
+            } => {
+                write!(
+                    f,
+                    "read of {requested} bytes at position {pos} but only {available} available"
+                )
+            }
+            Self::Invalid { pos } => write!(f, "invalid value at position {pos}"),
+        }
+    }
+}
+
+impl core::error::Error for ReadError {}
+
+/// Generate a `try_read_*` function and its infallible `read_*` counterpart for a primitive type,
+/// either in the byte order of the stream_buf or in a fixed byte order.
+macro_rules! read_primitive {
+    ($type:ty, $try_read:ident, $read:ident) => {
+        #[doc = concat!("Return ", stringify!($type), " read from the stream_buf in the stream_buf's byte order,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read(&mut self) -> Result<$type, ReadError> {
+            self.try_read_array_in_order().map(<$type>::from_le_bytes)
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read from the stream_buf in the stream_buf's byte order,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $read(&mut self) -> $type {
+            self.$try_read().unwrap_or_default()
+        }
+    };
+    ($type:ty, $try_read:ident, $read:ident, $from_bytes:ident, $endian:literal) => {
+        #[doc = concat!("Return ", $endian, " ", stringify!($type), " read from the stream_buf,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read(&mut self) -> Result<$type, ReadError> {
+            self.try_read_array().map(<$type>::$from_bytes)
+        }
+
+        #[doc = concat!("Return ", $endian, " ", stringify!($type), " read from the stream_buf,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $read(&mut self) -> $type {
+            self.$try_read().unwrap_or_default()
+        }
+    };
+}
+
+/// Generate functions that read a primitive type without advancing the position,
+/// either at the current position or at an absolute offset.
+macro_rules! peek_primitive {
+    ($type:ty, $try_read:ident, $try_peek:ident, $peek:ident, $try_read_at:ident, $read_at:ident) => {
+        #[doc = concat!("Return ", stringify!($type), " at the current position without advancing the position,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_peek(&self) -> Result<$type, ReadError> {
+            self.$try_read_at(self.pos)
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " at the current position without advancing the position,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $peek(&self) -> $type {
+            self.$try_peek().unwrap_or_default()
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " at absolute `offset` without advancing the position,")]
+        #[doc = "or a ReadError if there are not enough bytes in the stream_buf."]
+        pub fn $try_read_at(&self, offset: usize) -> Result<$type, ReadError> {
+            self.reader_at(offset, size_of::<$type>())?.$try_read()
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " at absolute `offset` without advancing the position,")]
+        #[doc = "or zero if there are not enough bytes in the stream_buf."]
+        pub fn $read_at(&self, offset: usize) -> $type {
+            self.$try_read_at(offset).unwrap_or_default()
+        }
+    };
+}
+
 /// Simple deserializer
-pub struct StreamBufReader<'a> {
+///
+/// The unsuffixed read functions use the byte order `E`, which defaults to little endian.
+#[derive(Clone)]
+pub struct StreamBufReader<'a, E = LittleEndian> {
     pos: usize,
     buf: &'a [u8],
+    byte_order: E,
 }
 
 /*The 'a notation in Rust is a lifetime parameter that tells the compiler how long a reference remains valid.
@@ -18,13 +125,39 @@ The name 'a is conventional; you can use others like 'b, but 'a is standard for
 */
 impl<'a> StreamBufReader<'a> {
     pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_byte_order(buf, LittleEndian)
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Create a reader whose unsuffixed read functions use `byte_order`.
+    /// ```
+    /// # use stream_buf::{BigEndian, StreamBufReader};
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c, 0x3d];
+    /// let mut sbuf_reader = StreamBufReader::with_byte_order(&buf, BigEndian);
+    ///
+    /// assert_eq!(0x0a1b, sbuf_reader.read_u16());
+    /// assert_eq!(0x3d2c, sbuf_reader.read_u16_little_endian());
+    /// ```
+    pub fn with_byte_order(buf: &'a [u8], byte_order: E) -> Self {
         Self {
             pos: 0,
             //size: buf.len(),
             buf,
+            byte_order,
         }
     }
 
+    pub fn byte_order(&self) -> E {
+        self.byte_order
+    }
+
+    /// Change the byte order, typically of a `DynamicEndian` reader once a header has announced it.
+    pub fn set_byte_order(&mut self, byte_order: E) {
+        self.byte_order = byte_order;
+    }
+
     /*pub fn new(buf: &'a [u8], bytes_written: usize) -> Self {
         Self {
             pos: 0,
@@ -64,7 +197,7 @@ impl<'a> StreamBufReader<'a> {
     }
 
     pub fn is_remaining(&self, size: usize) -> bool {
-        self.pos + size <= self.buf.len()
+        size <= self.buf.len().saturating_sub(self.pos)
     }
 
     pub fn bytes_read(&self) -> usize {
@@ -72,7 +205,7 @@ impl<'a> StreamBufReader<'a> {
     }
 
     pub fn advance(&mut self, n: usize) {
-        self.pos = (self.pos + n).min(self.buf.len());
+        self.pos = self.pos.saturating_add(n).min(self.buf.len());
     }
 
     pub fn get_ref(&self) -> &[u8] {
@@ -83,7 +216,148 @@ impl<'a> StreamBufReader<'a> {
         self.buf[index]
     }
 
+    /// Return Ok if `size` bytes are remaining, otherwise a ReadError describing the shortfall.
+    pub fn check_remaining(&self, size: usize) -> Result<(), ReadError> {
+        if self.is_remaining(size) {
+            return Ok(());
+        }
+        Err(ReadError::Truncated {
+            pos: self.pos,
+            requested: size,
+            available: self.bytes_remaining(),
+        })
+    }
+
+    fn try_read_array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        self.check_remaining(N)?;
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(&self.buf[self.pos..self.pos + N]);
+        self.pos += N;
+        Ok(bytes)
+    }
+
+    /// Read N bytes, reordered so they are always little endian.
+    fn try_read_array_in_order<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        let mut bytes = self.try_read_array()?;
+        if self.byte_order.is_big_endian() {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    /// Return a u8 read from the stream_buf, or a ReadError if there are no bytes remaining.
+    /// ```
+    /// # use stream_buf::{ReadError, StreamBufReader};
+    ///
+    /// let buf = [0x0a];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok(0x0a), sbuf_reader.try_read_u8());
+    /// assert_eq!(Err(ReadError::Truncated { pos: 1, requested: 1, available: 0 }), sbuf_reader.try_read_u8());
+    /// ```
+    pub fn try_read_u8(&mut self) -> Result<u8, ReadError> {
+        self.try_read_array().map(u8::from_le_bytes)
+    }
+
+    /// Return a u16 read from the stream_buf, or a ReadError if fewer than 2 bytes are remaining.
+    /// ```
+    /// # use stream_buf::{ReadError, StreamBufReader};
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok(0x1b0a), sbuf_reader.try_read_u16());
+    /// assert_eq!(Err(ReadError::Truncated { pos: 2, requested: 2, available: 1 }), sbuf_reader.try_read_u16());
+    /// ```
+    pub fn try_read_u16(&mut self) -> Result<u16, ReadError> {
+        self.try_read_array_in_order().map(u16::from_le_bytes)
+    }
+
+    /// Return a u32 read from the stream_buf, or a ReadError if fewer than 4 bytes are remaining.
+    pub fn try_read_u32(&mut self) -> Result<u32, ReadError> {
+        self.try_read_array_in_order().map(u32::from_le_bytes)
+    }
+
+    /// Return a big endian u16 read from the stream_buf, or a ReadError if fewer than 2 bytes are remaining.
+    pub fn try_read_u16_big_endian(&mut self) -> Result<u16, ReadError> {
+        self.try_read_array().map(u16::from_be_bytes)
+    }
+
+    /// Return a big endian u32 read from the stream_buf, or a ReadError if fewer than 4 bytes are remaining.
+    pub fn try_read_u32_big_endian(&mut self) -> Result<u32, ReadError> {
+        self.try_read_array().map(u32::from_be_bytes)
+    }
+
+    /// Return an f32 read from the stream_buf, or a ReadError if fewer than 4 bytes are remaining.
+    pub fn try_read_f32(&mut self) -> Result<f32, ReadError> {
+        self.try_read_u32().map(f32::from_bits)
+    }
+
+    /// Fill `dst` from the stream_buf, or return a ReadError if there are not enough bytes remaining.
+    /// On error nothing is read.
+    pub fn try_read(&mut self, dst: &mut [u8]) -> Result<(), ReadError> {
+        let read_size = dst.len();
+        self.check_remaining(read_size)?;
+        dst.copy_from_slice(&self.buf[self.pos..self.pos + read_size]);
+        self.pos += read_size;
+        Ok(())
+    }
+
+    /// Return a value of any type implementing `Decode` read from the stream_buf.
+    /// On error the position is left unchanged, so a partially decoded value is never consumed.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c, 0x01];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok((0x1b0a, [0x2c, 0x01])), sbuf_reader.get::<(u16, [u8; 2])>());
+    /// ```
+    pub fn get<T: Decode>(&mut self) -> Result<T, ReadError> {
+        let pos = self.pos;
+        let result = T::decode(self);
+        if result.is_err() {
+            self.pos = pos;
+        }
+        result
+    }
+
+    /// Return a value of any type implementing `Decode` read from the stream_buf, without advancing the position.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c, 0x01];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok((0x0a, 0x1b)), sbuf_reader.peek::<(u8, u8)>());
+    /// assert_eq!(0, sbuf_reader.pos());
+    /// ```
+    pub fn peek<T: Decode>(&self) -> Result<T, ReadError> {
+        self.get_at(self.pos)
+    }
+
+    /// Return a value of any type implementing `Decode` read from absolute `offset`, without advancing the position.
+    pub fn get_at<T: Decode>(&self, offset: usize) -> Result<T, ReadError> {
+        self.reader_at(offset, size_of::<T>())?.get()
+    }
+
+    /// Return a copy of this reader positioned at `offset`, or a ReadError if `offset` is past the end of the
+    /// stream_buf. `size` is the number of bytes to be read, and is only used to describe the error.
+    fn reader_at(&self, offset: usize, size: usize) -> Result<Self, ReadError> {
+        if offset > self.buf.len() {
+            return Err(ReadError::Truncated {
+                pos: offset,
+                requested: size,
+                available: 0,
+            });
+        }
+        let mut reader = self.clone();
+        reader.pos = offset;
+        Ok(reader)
+    }
+
     /// Return a u8 read from the stream_buf.
+    /// Returns 0 if there are no bytes remaining, use `try_read_u8` to distinguish this from a genuine zero.
     /// ```
     /// # use stream_buf::StreamBufReader;
     ///
@@ -95,13 +369,7 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(0x0a, v);
     /// ```
     pub fn read_u8(&mut self) -> u8 {
-        const READ_SIZE: usize = size_of::<u8>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0;
-        }
-        let pos = self.pos;
-        self.advance(READ_SIZE);
-        self.buf[pos]
+        self.try_read_u8().unwrap_or(0)
     }
 
     /// Return a u16 read from the stream_buf.
@@ -116,13 +384,7 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(0x1b0a, v);
     /// ```
     pub fn read_u16(&mut self) -> u16 {
-        const READ_SIZE: usize = size_of::<u16>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0;
-        }
-        let pos = self.pos;
-        self.advance(READ_SIZE);
-        u16::from_le_bytes([self.buf[pos], self.buf[pos + 1]])
+        self.try_read_u16().unwrap_or(0)
     }
 
     /// Return a u32 read from the stream_buf.
@@ -137,22 +399,7 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(0x3d2c1b0a, v);
     /// ```
     pub fn read_u32(&mut self) -> u32 {
-        const READ_SIZE: usize = size_of::<u32>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0;
-        }
-        let pos = self.pos;
-        self.advance(READ_SIZE);
-        u32::from_le_bytes([self.buf[pos], self.buf[pos + 1], self.buf[pos + 2], self.buf[pos + 3]])
-        /*
-        Alternatively:
-        u32::from_le_bytes(self.buf[pos..pos+4].try_into().unwrap())
-        let result = self.buf[pos..pos+4].try_into();
-        match result {
-            Ok(bytes) => { u32::from_le_bytes(bytes) },
-            Err(error) => { 0 },
-        }
-        */
+        self.try_read_u32().unwrap_or(0)
     }
 
     /// Return a u16 read from the stream_buf.
@@ -167,13 +414,7 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(0x0a1b, v);
     /// ```
     pub fn read_u16_big_endian(&mut self) -> u16 {
-        const READ_SIZE: usize = size_of::<u16>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0;
-        }
-        let pos = self.pos;
-        self.advance(READ_SIZE);
-        u16::from_be_bytes([self.buf[pos], self.buf[pos + 1]])
+        self.try_read_u16_big_endian().unwrap_or(0)
     }
 
     /// Return a u16 read from the stream_buf.
@@ -188,13 +429,7 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(0x0a1b2c3d, v);
     /// ```
     pub fn read_u32_big_endian(&mut self) -> u32 {
-        const READ_SIZE: usize = size_of::<u32>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0;
-        }
-        let pos = self.pos;
-        self.advance(READ_SIZE);
-        u32::from_be_bytes([self.buf[pos], self.buf[pos + 1], self.buf[pos + 2], self.buf[pos + 3]])
+        self.try_read_u32_big_endian().unwrap_or(0)
     }
 
     /// Return an f32 read from the stream_buf.
@@ -209,14 +444,173 @@ impl<'a> StreamBufReader<'a> {
     /// assert_eq!(1234.56, v);
     /// ```
     pub fn read_f32(&mut self) -> f32 {
-        const READ_SIZE: usize = size_of::<f32>();
-        if !self.is_remaining(READ_SIZE) {
-            return 0.0;
-        }
-        let bits = self.read_u32();
-        f32::from_bits(bits)
+        self.try_read_f32().unwrap_or(0.0)
     }
 
+    read_primitive!(i8, try_read_i8, read_i8);
+    read_primitive!(i16, try_read_i16, read_i16);
+    read_primitive!(i32, try_read_i32, read_i32);
+    read_primitive!(u64, try_read_u64, read_u64);
+    read_primitive!(i64, try_read_i64, read_i64);
+    read_primitive!(u128, try_read_u128, read_u128);
+    read_primitive!(i128, try_read_i128, read_i128);
+    read_primitive!(f64, try_read_f64, read_f64);
+
+    read_primitive!(
+        u16,
+        try_read_u16_little_endian,
+        read_u16_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        i16,
+        try_read_i16_little_endian,
+        read_i16_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        u32,
+        try_read_u32_little_endian,
+        read_u32_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        i32,
+        try_read_i32_little_endian,
+        read_i32_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        u64,
+        try_read_u64_little_endian,
+        read_u64_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        i64,
+        try_read_i64_little_endian,
+        read_i64_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        u128,
+        try_read_u128_little_endian,
+        read_u128_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        i128,
+        try_read_i128_little_endian,
+        read_i128_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        f32,
+        try_read_f32_little_endian,
+        read_f32_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+    read_primitive!(
+        f64,
+        try_read_f64_little_endian,
+        read_f64_little_endian,
+        from_le_bytes,
+        "a little endian"
+    );
+
+    read_primitive!(
+        i16,
+        try_read_i16_big_endian,
+        read_i16_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        i32,
+        try_read_i32_big_endian,
+        read_i32_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        u64,
+        try_read_u64_big_endian,
+        read_u64_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        i64,
+        try_read_i64_big_endian,
+        read_i64_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        u128,
+        try_read_u128_big_endian,
+        read_u128_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        i128,
+        try_read_i128_big_endian,
+        read_i128_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        f32,
+        try_read_f32_big_endian,
+        read_f32_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+    read_primitive!(
+        f64,
+        try_read_f64_big_endian,
+        read_f64_big_endian,
+        from_be_bytes,
+        "a big endian"
+    );
+
+    peek_primitive!(u8, try_read_u8, try_peek_u8, peek_u8, try_read_u8_at, read_u8_at);
+    peek_primitive!(i8, try_read_i8, try_peek_i8, peek_i8, try_read_i8_at, read_i8_at);
+    peek_primitive!(u16, try_read_u16, try_peek_u16, peek_u16, try_read_u16_at, read_u16_at);
+    peek_primitive!(i16, try_read_i16, try_peek_i16, peek_i16, try_read_i16_at, read_i16_at);
+    peek_primitive!(u32, try_read_u32, try_peek_u32, peek_u32, try_read_u32_at, read_u32_at);
+    peek_primitive!(i32, try_read_i32, try_peek_i32, peek_i32, try_read_i32_at, read_i32_at);
+    peek_primitive!(u64, try_read_u64, try_peek_u64, peek_u64, try_read_u64_at, read_u64_at);
+    peek_primitive!(i64, try_read_i64, try_peek_i64, peek_i64, try_read_i64_at, read_i64_at);
+    peek_primitive!(
+        u128,
+        try_read_u128,
+        try_peek_u128,
+        peek_u128,
+        try_read_u128_at,
+        read_u128_at
+    );
+    peek_primitive!(
+        i128,
+        try_read_i128,
+        try_peek_i128,
+        peek_i128,
+        try_read_i128_at,
+        read_i128_at
+    );
+    peek_primitive!(f32, try_read_f32, try_peek_f32, peek_f32, try_read_f32_at, read_f32_at);
+    peek_primitive!(f64, try_read_f64, try_peek_f64, peek_f64, try_read_f64_at, read_f64_at);
+
     /// Read an array from the stream_buf.
     /// Return the length read.
     /// ```
@@ -233,17 +627,163 @@ impl<'a> StreamBufReader<'a> {
     /// ```
     pub fn read(&mut self, dst: &mut [u8]) -> usize {
         let read_size = dst.len();
-        if !self.is_remaining(read_size) {
-            return 0;
+        match self.try_read(dst) {
+            Ok(()) => read_size,
+            Err(_) => 0,
         }
-        dst.copy_from_slice(&self.buf[self.pos..self.pos + read_size]);
-        self.pos += read_size;
-        read_size
+    }
+
+    /// Return the next `len` bytes of the stream_buf without copying, or a ReadError if there are not enough bytes
+    /// remaining. The slice borrows from the underlying buffer, so it outlives the reader.
+    pub fn try_read_slice(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        self.check_remaining(len)?;
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    /// Return the next `len` bytes of the stream_buf without copying, or None if there are not enough bytes remaining.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c, 0x3d];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Some(&buf[0..3]), sbuf_reader.read_slice(3));
+    /// assert_eq!(None, sbuf_reader.read_slice(2));
+    /// ```
+    pub fn read_slice(&mut self, len: usize) -> Option<&'a [u8]> {
+        self.try_read_slice(len).ok()
+    }
+
+    /// Return the bytes that have not yet been read, without advancing.
+    pub fn remaining_slice(&self) -> &'a [u8] {
+        self.buf.get(self.pos..).unwrap_or_default()
+    }
+
+    /// Return a reader over exactly the next `n` bytes and advance past them, or a ReadError if there are not enough
+    /// bytes remaining. The child reader has the byte order of the parent and reports positions relative to its own
+    /// start.
+    pub fn try_take(&mut self, n: usize) -> Result<StreamBufReader<'a, E>, ReadError> {
+        let buf = self.try_read_slice(n)?;
+        Ok(StreamBufReader::with_byte_order(buf, self.byte_order))
+    }
+
+    /// Return a reader over exactly the next `n` bytes and advance past them, or None if there are not enough bytes
+    /// remaining. A sub-parser given the child reader cannot consume the bytes of the next record.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// // two records, each a type byte, a length byte and a payload
+    /// let buf = [0x01, 0x02, 0x0a, 0x1b, 0x02, 0x03, 0x2c];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(0x01, sbuf_reader.read_u8());
+    /// let len = sbuf_reader.read_u8() as usize;
+    /// let mut payload = sbuf_reader.take(len).unwrap();
+    /// assert_eq!(0x0a, payload.read_u8());
+    /// assert_eq!(0x1b, payload.read_u8());
+    /// assert!(payload.try_read_u8().is_err());
+    ///
+    /// assert_eq!(0x02, sbuf_reader.read_u8());
+    /// let len = sbuf_reader.read_u8() as usize;
+    /// assert!(sbuf_reader.take(len).is_none());
+    /// ```
+    pub fn take(&mut self, n: usize) -> Option<StreamBufReader<'a, E>> {
+        self.try_take(n).ok()
+    }
+
+    /// Split the remaining bytes into a reader over the next `n` bytes and a reader over the rest, without advancing.
+    /// Returns None if there are fewer than `n` bytes remaining.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = [0x0a, 0x1b, 0x2c, 0x3d];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    /// sbuf_reader.advance(1);
+    ///
+    /// let (head, tail) = sbuf_reader.split_at(2).unwrap();
+    /// assert_eq!([0x1b, 0x2c], head.remaining_slice());
+    /// assert_eq!([0x3d], tail.remaining_slice());
+    /// assert_eq!(1, sbuf_reader.pos());
+    /// ```
+    pub fn split_at(&self, n: usize) -> Option<(StreamBufReader<'a, E>, StreamBufReader<'a, E>)> {
+        let (head, tail) = self.remaining_slice().split_at_checked(n)?;
+        Some((
+            StreamBufReader::with_byte_order(head, self.byte_order),
+            StreamBufReader::with_byte_order(tail, self.byte_order),
+        ))
+    }
+
+    /// Return a string of `len` bytes borrowed from the stream_buf.
+    /// Returns `ReadError::Invalid`, with the position of the first invalid byte, if the bytes are not valid UTF-8.
+    /// On error the position is left unchanged.
+    /// ```
+    /// # use stream_buf::{ReadError, StreamBufReader};
+    ///
+    /// let buf = *b"Hello\xff";
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok("Hel"), sbuf_reader.read_str(3));
+    /// assert_eq!(Err(ReadError::Invalid { pos: 5 }), sbuf_reader.read_str(3));
+    /// assert_eq!(Ok("lo"), sbuf_reader.read_str(2));
+    /// ```
+    pub fn read_str(&mut self, len: usize) -> Result<&'a str, ReadError> {
+        let pos = self.pos;
+        let bytes = self.try_read_slice(len)?;
+        core::str::from_utf8(bytes).map_err(|error| {
+            self.pos = pos;
+            ReadError::Invalid {
+                pos: pos + error.valid_up_to(),
+            }
+        })
+    }
+
+    /// Return a zero terminated string borrowed from the stream_buf, as written by `write_str_with_zero_terminator`.
+    /// The returned string excludes the terminator, the position is advanced past it.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = *b"Hello\0World";
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok("Hello"), sbuf_reader.read_cstr());
+    /// assert!(sbuf_reader.read_cstr().is_err());
+    /// assert_eq!(6, sbuf_reader.pos());
+    /// ```
+    pub fn read_cstr(&mut self) -> Result<&'a str, ReadError> {
+        let remaining = &self.buf[self.pos.min(self.buf.len())..];
+        let Some(len) = remaining.iter().position(|&byte| byte == 0) else {
+            return Err(ReadError::Truncated {
+                pos: self.pos,
+                requested: remaining.len() + 1,
+                available: remaining.len(),
+            });
+        };
+        let s = self.read_str(len)?;
+        self.pos += 1;
+        Ok(s)
+    }
+
+    /// Return a string borrowed from the stream_buf, preceded by its length as a u8.
+    /// On error the position is left unchanged.
+    /// ```
+    /// # use stream_buf::StreamBufReader;
+    ///
+    /// let buf = *b"\x05Hello";
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok("Hello"), sbuf_reader.read_pascal_str());
+    /// ```
+    pub fn read_pascal_str(&mut self) -> Result<&'a str, ReadError> {
+        let pos = self.pos;
+        let len = self.try_read_u8()?;
+        self.read_str(len.into()).inspect_err(|_| self.pos = pos)
     }
 }
 
 /// Access StreamBuf component by index
-impl<'a> Index<usize> for StreamBufReader<'a> {
+impl<'a, E> Index<usize> for StreamBufReader<'a, E> {
     type Output = u8;
     fn index(&self, index: usize) -> &u8 {
         &self.buf[index]
@@ -253,6 +793,7 @@ impl<'a> Index<usize> for StreamBufReader<'a> {
 #[cfg(any(debug_assertions, test))]
 mod tests {
     use super::*;
+    use crate::byte_order::BigEndian;
 
     #[test]
     fn new() {
@@ -323,4 +864,261 @@ mod tests {
         assert_eq!(5, len);
         assert_eq!([0x0a, 0x1b, 0x2c, 0x3d, 0x4e], data);
     }
+
+    #[test]
+    fn try_read() {
+        let buf = [0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Ok(0x0a), sbuf_reader.try_read_u8());
+        assert_eq!(Ok(0x2c1b), sbuf_reader.try_read_u16());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 3,
+                requested: 8,
+                available: 4
+            }),
+            sbuf_reader.try_read(&mut [0u8; 8])
+        );
+        assert_eq!(3, sbuf_reader.pos());
+        assert_eq!(Ok(0x605f4e3d), sbuf_reader.try_read_u32());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 7,
+                requested: 1,
+                available: 0
+            }),
+            sbuf_reader.try_read_u8()
+        );
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 7,
+                requested: 4,
+                available: 0
+            }),
+            sbuf_reader.try_read_f32()
+        );
+
+        sbuf_reader.reset();
+        assert_eq!(Ok(0x0a1b2c3d), sbuf_reader.try_read_u32_big_endian());
+        assert_eq!(Ok(0x4e5f), sbuf_reader.try_read_u16_big_endian());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 6,
+                requested: 2,
+                available: 1
+            }),
+            sbuf_reader.try_read_u16_big_endian()
+        );
+        // a failed read does not advance the position
+        assert_eq!(6, sbuf_reader.pos());
+        assert_eq!(0x60, sbuf_reader.read_u8());
+    }
+
+    #[test]
+    fn try_read_f32() {
+        let buf = [0xec, 0x51, 0x9a];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 0,
+                requested: 4,
+                available: 3
+            }),
+            sbuf_reader.try_read_f32()
+        );
+        assert_eq!(0.0, sbuf_reader.read_f32());
+    }
+
+    #[test]
+    fn read_signed_and_wide() {
+        let buf = [0xfe, 0xff, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(-2, sbuf_reader.read_i16());
+        assert_eq!(-128, sbuf_reader.read_i8());
+        sbuf_reader.reset();
+        assert_eq!(-257, sbuf_reader.read_i16_big_endian());
+        sbuf_reader.reset();
+        assert_eq!(0x0000_0000_0080_fffe, sbuf_reader.read_u64());
+        sbuf_reader.reset();
+        sbuf_reader.advance(2);
+        assert_eq!(i64::MIN + 1, sbuf_reader.read_i64_big_endian());
+        assert_eq!(0, sbuf_reader.read_u64());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 10,
+                requested: 16,
+                available: 0
+            }),
+            sbuf_reader.try_read_u128()
+        );
+    }
+
+    #[test]
+    fn read_f64() {
+        let buf = [0x40, 0x93, 0x4a, 0x3d, 0x70, 0xa3, 0xd7, 0x0a];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(1234.56, sbuf_reader.read_f64_big_endian());
+        sbuf_reader.reset();
+        assert_eq!(f64::from_bits(0x0ad7a3703d4a9340), sbuf_reader.read_f64());
+    }
+
+    #[test]
+    fn peek() {
+        let buf = [0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(0x0a, sbuf_reader.peek_u8());
+        assert_eq!(0x1b0a, sbuf_reader.peek_u16());
+        assert_eq!(0, sbuf_reader.pos());
+        sbuf_reader.advance(4);
+        assert_eq!(Ok(0x4e), sbuf_reader.try_peek_u8());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 4,
+                requested: 4,
+                available: 3
+            }),
+            sbuf_reader.try_peek_u32()
+        );
+        assert_eq!(0, sbuf_reader.peek_u32());
+        assert_eq!(4, sbuf_reader.pos());
+        assert_eq!(0x4e, sbuf_reader.read_u8());
+    }
+
+    #[test]
+    fn read_at() {
+        let buf = [0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60];
+        let mut sbuf_reader = StreamBufReader::with_byte_order(&buf, BigEndian);
+        sbuf_reader.advance(5);
+        assert_eq!(0x1b2c, sbuf_reader.read_u16_at(1));
+        assert_eq!(Ok(0x3d4e5f60), sbuf_reader.try_read_u32_at(3));
+        assert_eq!(Ok([0x5f, 0x60]), sbuf_reader.get_at::<[u8; 2]>(5));
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 4,
+                requested: 4,
+                available: 3
+            }),
+            sbuf_reader.try_read_i32_at(4)
+        );
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 9,
+                requested: 1,
+                available: 0
+            }),
+            sbuf_reader.try_read_u8_at(9)
+        );
+        // offsets near usize::MAX, as might be read from a corrupt packet, must not overflow
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: usize::MAX,
+                requested: 2,
+                available: 0
+            }),
+            sbuf_reader.try_read_u16_at(usize::MAX)
+        );
+        assert!(sbuf_reader.try_read_u16_at(usize::MAX - 1).is_err());
+        assert!(sbuf_reader.get_at::<[u8; 0]>(usize::MAX).is_err());
+        assert!(sbuf_reader.get_at::<u32>(usize::MAX - 1).is_err());
+        assert_eq!(5, sbuf_reader.pos());
+        assert_eq!(Ok(0x5f60), sbuf_reader.peek::<u16>());
+    }
+
+    #[test]
+    fn read_slice() {
+        let buf = [0x0a, 0x1b, 0x2c, 0x3d, 0x4e];
+        let slice = {
+            let mut sbuf_reader = StreamBufReader::new(&buf);
+            assert_eq!(Some(&[0x0a][..]), sbuf_reader.read_slice(1));
+            assert_eq!(
+                Err(ReadError::Truncated {
+                    pos: 1,
+                    requested: 5,
+                    available: 4
+                }),
+                sbuf_reader.try_read_slice(5)
+            );
+            assert_eq!(Some(&[][..]), sbuf_reader.read_slice(0));
+            sbuf_reader.read_slice(4)
+        };
+        // the slice outlives the reader
+        assert_eq!(Some(&[0x1b, 0x2c, 0x3d, 0x4e][..]), slice);
+    }
+
+    #[test]
+    fn take() {
+        let buf = [0x02, 0x0a, 0x1b, 0x03, 0x2c, 0x3d, 0x4e];
+        let mut sbuf_reader = StreamBufReader::with_byte_order(&buf, BigEndian);
+        let len = sbuf_reader.read_u8() as usize;
+        let mut record = sbuf_reader
+            .take(len)
+            .unwrap_or(StreamBufReader::with_byte_order(&[], BigEndian));
+        assert_eq!(3, sbuf_reader.pos());
+        assert_eq!(0x0a1b, record.read_u16());
+        // the child reader cannot run into the next record
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 2,
+                requested: 1,
+                available: 0
+            }),
+            record.try_read_u8()
+        );
+        assert_eq!(0, record.read_u8());
+
+        let len = sbuf_reader.read_u8() as usize;
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 4,
+                requested: 5,
+                available: 3
+            }),
+            sbuf_reader.try_take(len + 2).map(|record| record.pos())
+        );
+        assert_eq!(4, sbuf_reader.pos());
+        assert_eq!([0x2c, 0x3d, 0x4e], sbuf_reader.remaining_slice());
+        assert_eq!(
+            Some(([0x2c, 0x3d].as_slice(), [0x4e].as_slice())),
+            sbuf_reader
+                .split_at(2)
+                .map(|(head, tail)| (head.remaining_slice(), tail.remaining_slice()))
+        );
+        assert!(sbuf_reader.split_at(4).is_none());
+        assert_eq!(Some(3), sbuf_reader.take(3).map(|record| record.bytes_remaining()));
+        assert_eq!(0, sbuf_reader.remaining_slice().len());
+    }
+
+    #[test]
+    fn read_strings() {
+        let buf = *b"\x03abcdef\0\x05gh";
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Ok("abc"), sbuf_reader.read_pascal_str());
+        assert_eq!(Ok("def"), sbuf_reader.read_cstr());
+        assert_eq!(8, sbuf_reader.pos());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 9,
+                requested: 5,
+                available: 2
+            }),
+            sbuf_reader.read_pascal_str()
+        );
+        assert_eq!(8, sbuf_reader.pos());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 8,
+                requested: 4,
+                available: 3
+            }),
+            sbuf_reader.read_cstr()
+        );
+        sbuf_reader.advance(1);
+        assert_eq!(Ok("gh"), sbuf_reader.read_str(2));
+        assert_eq!(Ok(""), sbuf_reader.read_str(0));
+
+        let buf = [b'a', 0xc3, 0x28, 0];
+        let mut sbuf_reader = StreamBufReader::new(&buf);
+        assert_eq!(Err(ReadError::Invalid { pos: 1 }), sbuf_reader.read_cstr());
+        assert_eq!(0, sbuf_reader.pos());
+    }
 }
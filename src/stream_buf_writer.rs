@@ -1,13 +1,112 @@
 #![allow(unused)]
 
+use crate::byte_order::{ByteOrder, LittleEndian};
+use crate::encode::Encode;
 use crate::stream_buf_reader::StreamBufReader;
+use crate::transaction::Transaction;
+use core::fmt;
 use core::mem;
 use core::ops::{Index, IndexMut};
 
+/// Error returned by the `try_write_*` functions when there is not enough space available in the stream_buf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteError {
+    /// Position in the stream_buf at which the write was attempted.
+    pub pos: usize,
+    /// Number of bytes requested.
+    pub requested: usize,
+    /// Number of bytes actually available.
+    pub available: usize,
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "write of {} bytes at position {} but only {} available",
+            self.requested, self.pos, self.available
+        )
+    }
+}
+
+impl core::error::Error for WriteError {}
+
+/// State of a StreamBufWriter, returned by `checkpoint()` and restored by `rollback()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pos: usize,
+    overflow: Option<WriteError>,
+    drops: usize,
+}
+
+impl Checkpoint {
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+}
+
+/// Generate a `try_write_*` function and its infallible `write_*` counterpart for a primitive type,
+/// either in the byte order of the stream_buf or in a fixed byte order.
+macro_rules! write_primitive {
+    ($type:ty, $try_write:ident, $write:ident) => {
+        #[doc = concat!("Write ", stringify!($type), " to the stream_buf in the stream_buf's byte order,")]
+        #[doc = "or return a WriteError if there is not enough space available."]
+        pub fn $try_write(&mut self, value: $type) -> Result<(), WriteError> {
+            self.try_write_array_in_order(value.to_le_bytes())
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " to the stream_buf in the stream_buf's byte order.")]
+        #[doc = "The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write(&mut self, value: $type) {
+            let _ = self.$try_write(value);
+        }
+    };
+    ($type:ty, $try_write:ident, $write:ident, $to_bytes:ident, $endian:literal) => {
+        #[doc = concat!("Write ", $endian, " ", stringify!($type), " to the stream_buf,")]
+        #[doc = "or return a WriteError if there is not enough space available."]
+        pub fn $try_write(&mut self, value: $type) -> Result<(), WriteError> {
+            self.try_write(&value.$to_bytes())
+        }
+
+        #[doc = concat!("Write ", $endian, " ", stringify!($type), " to the stream_buf.")]
+        #[doc = "The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write(&mut self, value: $type) {
+            let _ = self.$try_write(value);
+        }
+    };
+}
+
+/// Generate functions that overwrite a primitive type at an absolute offset within the bytes already written.
+macro_rules! write_primitive_at {
+    ($type:ty, $try_write:ident, $try_write_at:ident, $write_at:ident) => {
+        #[doc = concat!("Overwrite ", stringify!($type), " at absolute `offset` in the stream_buf's byte order,")]
+        #[doc = "without changing the position. Return a WriteError if it would extend past the bytes written."]
+        pub fn $try_write_at(&mut self, offset: usize, value: $type) -> Result<(), WriteError> {
+            self.writer_at(offset, size_of::<$type>())?.$try_write(value)
+        }
+
+        #[doc = concat!("Overwrite ", stringify!($type), " at absolute `offset` in the stream_buf's byte order,")]
+        #[doc = "without changing the position. The value is dropped and the overflow flag set if it would extend"]
+        #[doc = "past the bytes written."]
+        pub fn $write_at(&mut self, offset: usize, value: $type) {
+            let _ = self.$try_write_at(offset, value);
+        }
+    };
+}
+
 /// Simple serializer/deserializer
-pub struct StreamBufWriter<'a> {
+///
+/// The unsuffixed write functions use the byte order `E`, which defaults to little endian.
+pub struct StreamBufWriter<'a, E = LittleEndian> {
     pos: usize,
     buf: &'a mut [u8],
+    overflow: Option<WriteError>,
+    /// Number of writes dropped, used to detect drops after the overflow flag has already been set.
+    drops: usize,
+    last_drop: Option<WriteError>,
+    /// Number of times the position has been moved back, so that written bytes may since have been overwritten.
+    rewinds: usize,
+    byte_order: E,
 }
 
 /*The 'a notation in Rust is a lifetime parameter that tells the compiler how long a reference remains valid.
@@ -19,7 +118,41 @@ The name 'a is conventional; you can use others like 'b, but 'a is standard for
 */
 impl<'a> StreamBufWriter<'a> {
     pub fn new(buf: &'a mut [u8]) -> Self {
-        Self { pos: 0, buf }
+        Self::with_byte_order(buf, LittleEndian)
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Create a writer whose unsuffixed write functions use `byte_order`.
+    /// ```
+    /// # use stream_buf::{BigEndian, StreamBufWriter};
+    /// let mut data = [0u8; 4];
+    /// let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+    ///
+    /// sbuf_writer.write_u16(0x0a1b);
+    /// sbuf_writer.write_u16_little_endian(0x2c3d);
+    ///
+    /// assert_eq!([0x0a, 0x1b, 0x3d, 0x2c], data);
+    /// ```
+    pub fn with_byte_order(buf: &'a mut [u8], byte_order: E) -> Self {
+        Self {
+            pos: 0,
+            buf,
+            overflow: None,
+            drops: 0,
+            last_drop: None,
+            rewinds: 0,
+            byte_order,
+        }
+    }
+
+    pub fn byte_order(&self) -> E {
+        self.byte_order
+    }
+
+    /// Change the byte order, typically of a `DynamicEndian` writer.
+    pub fn set_byte_order(&mut self, byte_order: E) {
+        self.byte_order = byte_order;
     }
 
     pub fn get_data(&self) -> &[u8] {
@@ -34,8 +167,105 @@ impl<'a> StreamBufWriter<'a> {
         self.pos
     }
 
+    /// Reset the position to the start of the stream_buf and clear the overflow flag.
     pub fn reset(&mut self) {
-        self.pos = 0;
+        self.rewind(0);
+        self.overflow = None;
+    }
+
+    /// Move the position back to `pos`, recording that the bytes after it may be overwritten.
+    fn rewind(&mut self, pos: usize) {
+        if pos < self.pos {
+            self.rewinds = self.rewinds.wrapping_add(1);
+        }
+        self.pos = pos;
+    }
+
+    /// Return a count that changes whenever the position is moved back.
+    pub(crate) fn rewinds(&self) -> usize {
+        self.rewinds
+    }
+
+    /// Return true if any write since the last `reset()` was dropped because there was not enough space.
+    /// This allows a whole frame to be built and then checked once before it is transmitted.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 4];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.write_u16(0x0a1b);
+    /// assert!(!sbuf_writer.overflowed());
+    /// sbuf_writer.write_u32(0x0a1b2c3d);
+    /// sbuf_writer.write_u8(0x0a);
+    /// assert!(sbuf_writer.overflowed());
+    /// assert_eq!(3, sbuf_writer.bytes_written());
+    ///
+    /// sbuf_writer.reset();
+    /// assert!(!sbuf_writer.overflowed());
+    /// ```
+    pub fn overflowed(&self) -> bool {
+        self.overflow.is_some()
+    }
+
+    /// Return the first write since the last `reset()` that was dropped because there was not enough space.
+    pub fn overflow_error(&self) -> Option<WriteError> {
+        self.overflow
+    }
+
+    /// Record the current position and overflow flag, so that subsequent writes can be undone with `rollback()`.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 4];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.write_u16(0x0a1b);
+    /// let checkpoint = sbuf_writer.checkpoint();
+    /// sbuf_writer.write_u8(0x2c);
+    /// sbuf_writer.write_u16(0x3d4e); // does not fit
+    /// assert!(sbuf_writer.overflowed());
+    ///
+    /// sbuf_writer.rollback(checkpoint);
+    /// assert!(!sbuf_writer.overflowed());
+    /// assert_eq!([0x1b, 0x0a], sbuf_writer.get_ref());
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            pos: self.pos,
+            overflow: self.overflow,
+            drops: self.drops,
+        }
+    }
+
+    /// Discard everything written since `checkpoint` was taken and restore the overflow flag.
+    /// The position is never advanced, so a checkpoint taken before a `reset()` cannot expose stale data.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        if checkpoint.pos <= self.pos {
+            self.rewind(checkpoint.pos);
+            self.overflow = checkpoint.overflow;
+            self.drops = checkpoint.drops;
+        }
+    }
+
+    /// Return the last write dropped since `checkpoint` was taken, as an error, or Ok if none was dropped.
+    /// Unlike the overflow flag, this detects drops even if a write had already been dropped before the checkpoint.
+    pub(crate) fn check_no_drops_since(&self, checkpoint: &Checkpoint) -> Result<(), WriteError> {
+        match self.last_drop {
+            Some(error) if self.drops != checkpoint.drops => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Set the overflow flag, if not already set, and record that a write was dropped.
+    fn record_drop(&mut self, error: WriteError) {
+        self.overflow.get_or_insert(error);
+        self.drops = self.drops.wrapping_add(1);
+        self.last_drop = Some(error);
+    }
+
+    /// Return a guard that gives access to the writer and rolls back everything written through it when dropped,
+    /// unless `Transaction::commit()` is called.
+    pub fn transaction(&mut self) -> Transaction<'_, 'a, E> {
+        Transaction::new(self)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -54,7 +284,7 @@ impl<'a> StreamBufWriter<'a> {
     }
 
     pub fn is_available(&self, size: usize) -> bool {
-        self.pos + size <= self.buf.len()
+        size <= self.buf.len().saturating_sub(self.pos)
     }
 
     pub fn bytes_written(&self) -> usize {
@@ -62,7 +292,7 @@ impl<'a> StreamBufWriter<'a> {
     }
 
     pub fn advance(&mut self, n: usize) {
-        self.pos = (self.pos + n).min(self.buf.len());
+        self.pos = self.pos.saturating_add(n).min(self.buf.len());
     }
 
     pub fn get_ref(&self) -> &[u8] {
@@ -73,12 +303,139 @@ impl<'a> StreamBufWriter<'a> {
         self.buf[index]
     }
 
-    pub fn write_u8(&mut self, value: u8) {
-        const WRITE_SIZE: usize = size_of::<u8>();
-        if self.is_available(WRITE_SIZE) {
-            self.buf[self.pos] = value;
-            self.pos += 1;
+    /// Return Ok if `size` bytes are available, otherwise a WriteError describing the shortfall.
+    pub fn check_available(&self, size: usize) -> Result<(), WriteError> {
+        if self.is_available(size) {
+            return Ok(());
         }
+        Err(WriteError {
+            pos: self.pos,
+            requested: size,
+            available: self.bytes_remaining(),
+        })
+    }
+
+    fn check_available_or_overflow(&mut self, size: usize) -> Result<(), WriteError> {
+        let result = self.check_available(size);
+        if let Err(error) = result {
+            self.record_drop(error);
+        }
+        result
+    }
+
+    /// Write `src` to the stream_buf, or return a WriteError if there is not enough space available.
+    /// On error nothing is written and the overflow flag is set.
+    /// ```
+    /// # use stream_buf::{StreamBufWriter, WriteError};
+    /// let mut data = [0u8; 4];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// assert_eq!(Ok(()), sbuf_writer.try_write(&[0x0a, 0x1b, 0x2c]));
+    /// assert_eq!(
+    ///     Err(WriteError { pos: 3, requested: 2, available: 1 }),
+    ///     sbuf_writer.try_write(&[0x3d, 0x4e])
+    /// );
+    /// assert!(sbuf_writer.overflowed());
+    /// ```
+    pub fn try_write(&mut self, src: &[u8]) -> Result<(), WriteError> {
+        let write_size = src.len();
+        self.check_available_or_overflow(write_size)?;
+        self.buf[self.pos..self.pos + write_size].copy_from_slice(src);
+        self.pos += write_size;
+        Ok(())
+    }
+
+    /// Write N little endian bytes, reordered to the byte order of the stream_buf.
+    fn try_write_array_in_order<const N: usize>(&mut self, mut bytes: [u8; N]) -> Result<(), WriteError> {
+        if self.byte_order.is_big_endian() {
+            bytes.reverse();
+        }
+        self.try_write(&bytes)
+    }
+
+    /// Write a u8 to the stream_buf, or return a WriteError if there is no space available.
+    pub fn try_write_u8(&mut self, value: u8) -> Result<(), WriteError> {
+        self.try_write(&value.to_le_bytes())
+    }
+
+    /// Write a u16 to the stream_buf, or return a WriteError if there is not enough space available.
+    /// ```
+    /// # use stream_buf::{StreamBufWriter, WriteError};
+    /// let mut data = [0u8; 3];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// assert_eq!(Ok(()), sbuf_writer.try_write_u16(0x0a1b));
+    /// assert_eq!(
+    ///     Err(WriteError { pos: 2, requested: 2, available: 1 }),
+    ///     sbuf_writer.try_write_u16(0x2c3d)
+    /// );
+    /// ```
+    pub fn try_write_u16(&mut self, value: u16) -> Result<(), WriteError> {
+        self.try_write_array_in_order(value.to_le_bytes())
+    }
+
+    /// Write a u32 to the stream_buf, or return a WriteError if there is not enough space available.
+    pub fn try_write_u32(&mut self, value: u32) -> Result<(), WriteError> {
+        self.try_write_array_in_order(value.to_le_bytes())
+    }
+
+    /// Write a u16 to the stream_buf, big endian, or return a WriteError if there is not enough space available.
+    pub fn try_write_u16_big_endian(&mut self, value: u16) -> Result<(), WriteError> {
+        self.try_write(&value.to_be_bytes())
+    }
+
+    /// Write a u32 to the stream_buf, big endian, or return a WriteError if there is not enough space available.
+    pub fn try_write_u32_big_endian(&mut self, value: u32) -> Result<(), WriteError> {
+        self.try_write(&value.to_be_bytes())
+    }
+
+    /// Write an f32 to the stream_buf, or return a WriteError if there is not enough space available.
+    pub fn try_write_f32(&mut self, value: f32) -> Result<(), WriteError> {
+        self.try_write_u32(value.to_bits())
+    }
+
+    /// Fill `len` bytes of the stream_buf with `data`, or return a WriteError if there is not enough space available.
+    pub fn try_fill(&mut self, data: u8, len: usize) -> Result<(), WriteError> {
+        self.check_available_or_overflow(len)?;
+        self.buf[self.pos..self.pos + len].fill(data);
+        self.pos += len;
+        Ok(())
+    }
+
+    /// Write a string to the stream_buf, or return a WriteError if there is not enough space available.
+    pub fn try_write_str(&mut self, src: &str) -> Result<(), WriteError> {
+        self.try_write(src.as_bytes())
+    }
+
+    /// Write a zero terminated string to the stream_buf, or return a WriteError if there is not enough space available.
+    pub fn try_write_str_with_zero_terminator(&mut self, src: &str) -> Result<(), WriteError> {
+        self.check_available_or_overflow(src.len() + 1)?;
+        self.try_write_str(src)?;
+        self.try_write_u8(0)
+    }
+
+    /// Write a value of any type implementing `Encode` to the stream_buf.
+    /// On error the position is left unchanged, so a partially encoded value is never left in the stream_buf.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 8];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.put(&(0x0a1bu16, [0x2cu8, 0x3d])).unwrap();
+    ///
+    /// assert_eq!([0x1b, 0x0a, 0x2c, 0x3d], data[0..4]);
+    /// ```
+    pub fn put<T: Encode + ?Sized>(&mut self, value: &T) -> Result<(), WriteError> {
+        let pos = self.pos;
+        let result = value.encode(self);
+        if result.is_err() {
+            self.rewind(pos);
+        }
+        result
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        let _ = self.try_write_u8(value);
     }
 
     /// Write a u16 to the streambuf.
@@ -93,13 +450,7 @@ impl<'a> StreamBufWriter<'a> {
     /// assert_eq!([0x1b,0x0a], data[0..2]);
     /// ```
     pub fn write_u16(&mut self, value: u16) {
-        const WRITE_SIZE: usize = size_of::<u16>();
-        if self.is_available(WRITE_SIZE) {
-            let bytes = value.to_le_bytes();
-            self.buf[self.pos] = bytes[0];
-            self.buf[self.pos + 1] = bytes[1];
-            self.pos += 2;
-        }
+        let _ = self.try_write_u16(value);
     }
 
     /// Write an u32 to the streambuf.
@@ -114,15 +465,7 @@ impl<'a> StreamBufWriter<'a> {
     /// assert_eq!([0x3d,0x2c,0x1b,0x0a], data[0..4]);
     /// ```
     pub fn write_u32(&mut self, value: u32) {
-        //let value: u32 = 0x12345678;
-        //let bytes: [u8; 4] = value.to_le_bytes(); // [0x78, 0x56, 0x34, 0x12]
-        const WRITE_SIZE: usize = size_of::<u32>();
-        if self.is_available(WRITE_SIZE) {
-            value.to_le_bytes().iter().for_each(|&byte| {
-                self.buf[self.pos] = byte;
-                self.pos += 1;
-            });
-        }
+        let _ = self.try_write_u32(value);
     }
 
     /// Write a u16 to the streambuf, big endian.
@@ -137,13 +480,7 @@ impl<'a> StreamBufWriter<'a> {
     /// assert_eq!([0x0a,0x1b], data[0..2]);
     /// ```
     pub fn write_u16_big_endian(&mut self, value: u16) {
-        const WRITE_SIZE: usize = size_of::<u16>();
-        if self.is_available(WRITE_SIZE) {
-            value.to_be_bytes().iter().for_each(|&byte| {
-                self.buf[self.pos] = byte;
-                self.pos += 1;
-            });
-        }
+        let _ = self.try_write_u16_big_endian(value);
     }
 
     /// Write an u32 to the streambuf, big endian.
@@ -158,13 +495,7 @@ impl<'a> StreamBufWriter<'a> {
     /// assert_eq!([0x0a,0x1b,0x2c,0x3d], data[0..4]);
     /// ```
     pub fn write_u32_big_endian(&mut self, value: u32) {
-        const WRITE_SIZE: usize = size_of::<u32>();
-        if self.is_available(WRITE_SIZE) {
-            value.to_be_bytes().iter().for_each(|&byte| {
-                self.buf[self.pos] = byte;
-                self.pos += 1;
-            });
-        }
+        let _ = self.try_write_u32_big_endian(value);
     }
 
     /// Write an f32 to the streambuf.
@@ -179,12 +510,216 @@ impl<'a> StreamBufWriter<'a> {
     /// assert_eq!([0xec, 0x51, 0x9a, 0x44], data[0..4]);
     /// ```
     pub fn write_f32(&mut self, value: f32) {
-        let bits = value.to_bits().cast_signed();
-        self.write_u32(bits as u32);
+        let _ = self.try_write_f32(value);
+    }
+
+    write_primitive!(i8, try_write_i8, write_i8);
+    write_primitive!(i16, try_write_i16, write_i16);
+    write_primitive!(i32, try_write_i32, write_i32);
+    write_primitive!(u64, try_write_u64, write_u64);
+    write_primitive!(i64, try_write_i64, write_i64);
+    write_primitive!(u128, try_write_u128, write_u128);
+    write_primitive!(i128, try_write_i128, write_i128);
+    write_primitive!(f64, try_write_f64, write_f64);
+
+    write_primitive!(
+        u16,
+        try_write_u16_little_endian,
+        write_u16_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        i16,
+        try_write_i16_little_endian,
+        write_i16_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        u32,
+        try_write_u32_little_endian,
+        write_u32_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        i32,
+        try_write_i32_little_endian,
+        write_i32_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        u64,
+        try_write_u64_little_endian,
+        write_u64_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        i64,
+        try_write_i64_little_endian,
+        write_i64_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        u128,
+        try_write_u128_little_endian,
+        write_u128_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        i128,
+        try_write_i128_little_endian,
+        write_i128_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        f32,
+        try_write_f32_little_endian,
+        write_f32_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+    write_primitive!(
+        f64,
+        try_write_f64_little_endian,
+        write_f64_little_endian,
+        to_le_bytes,
+        "a little endian"
+    );
+
+    write_primitive!(
+        i16,
+        try_write_i16_big_endian,
+        write_i16_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        i32,
+        try_write_i32_big_endian,
+        write_i32_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        u64,
+        try_write_u64_big_endian,
+        write_u64_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        i64,
+        try_write_i64_big_endian,
+        write_i64_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        u128,
+        try_write_u128_big_endian,
+        write_u128_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        i128,
+        try_write_i128_big_endian,
+        write_i128_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        f32,
+        try_write_f32_big_endian,
+        write_f32_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+    write_primitive!(
+        f64,
+        try_write_f64_big_endian,
+        write_f64_big_endian,
+        to_be_bytes,
+        "a big endian"
+    );
+
+    /// Return a writer over the `len` bytes at absolute `offset`, which must lie within the bytes already written.
+    pub(crate) fn writer_at(&mut self, offset: usize, len: usize) -> Result<StreamBufWriter<'_, E>, WriteError> {
+        self.writer_at_with_byte_order(offset, len, self.byte_order)
     }
 
+    /// Return a writer using `byte_order` over the `len` bytes at absolute `offset`, which must lie within the bytes
+    /// already written.
+    pub(crate) fn writer_at_with_byte_order<B: ByteOrder>(
+        &mut self,
+        offset: usize,
+        len: usize,
+        byte_order: B,
+    ) -> Result<StreamBufWriter<'_, B>, WriteError> {
+        if offset.checked_add(len).is_none_or(|end| end > self.pos) {
+            let error = WriteError {
+                pos: offset,
+                requested: len,
+                available: self.pos.saturating_sub(offset),
+            };
+            self.record_drop(error);
+            return Err(error);
+        }
+        Ok(StreamBufWriter::with_byte_order(
+            &mut self.buf[offset..offset + len],
+            byte_order,
+        ))
+    }
+
+    /// Overwrite a value of any type implementing `Encode` at absolute `offset`, without changing the position.
+    /// Return a WriteError if it would extend past the bytes written.
+    /// ```
+    /// # use stream_buf::StreamBufWriter;
+    /// let mut data = [0u8; 4];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// sbuf_writer.write_u32(0);
+    /// sbuf_writer.put_at(1, &[0x0a1bu16]).unwrap();
+    ///
+    /// assert_eq!([0x00, 0x1b, 0x0a, 0x00], sbuf_writer.get_ref());
+    /// assert!(sbuf_writer.put_at(3, &0x2c3du16).is_err());
+    /// ```
+    pub fn put_at<T: Encode + ?Sized>(&mut self, offset: usize, value: &T) -> Result<(), WriteError> {
+        let len = self.pos.saturating_sub(offset);
+        let result = self.writer_at(offset, len)?.put(value);
+        if let Err(error) = result {
+            let error = WriteError {
+                pos: offset + error.pos,
+                ..error
+            };
+            self.record_drop(error);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    write_primitive_at!(u8, try_write_u8, try_write_u8_at, write_u8_at);
+    write_primitive_at!(i8, try_write_i8, try_write_i8_at, write_i8_at);
+    write_primitive_at!(u16, try_write_u16, try_write_u16_at, write_u16_at);
+    write_primitive_at!(i16, try_write_i16, try_write_i16_at, write_i16_at);
+    write_primitive_at!(u32, try_write_u32, try_write_u32_at, write_u32_at);
+    write_primitive_at!(i32, try_write_i32, try_write_i32_at, write_i32_at);
+    write_primitive_at!(u64, try_write_u64, try_write_u64_at, write_u64_at);
+    write_primitive_at!(i64, try_write_i64, try_write_i64_at, write_i64_at);
+    write_primitive_at!(u128, try_write_u128, try_write_u128_at, write_u128_at);
+    write_primitive_at!(i128, try_write_i128, try_write_i128_at, write_i128_at);
+    write_primitive_at!(f32, try_write_f32, try_write_f32_at, write_f32_at);
+    write_primitive_at!(f64, try_write_f64, try_write_f64_at, write_f64_at);
+
     pub fn fill_without_advancing(&mut self, data: u8, len: usize) -> bool {
-        if (self.pos + len > self.buf.len()) {
+        if self.check_available_or_overflow(len).is_err() {
             return false;
         }
         self.buf[self.pos..self.pos + len].fill(data);
@@ -192,53 +727,30 @@ impl<'a> StreamBufWriter<'a> {
     }
 
     pub fn fill(&mut self, data: u8, len: usize) {
-        if self.fill_without_advancing(data, len) {
-            self.pos += len;
-        }
+        let _ = self.try_fill(data, len);
     }
 
     pub fn write(&mut self, src: &[u8]) -> usize {
-        let write_size = src.len();
-        if self.is_available(write_size) {
-            self.buf[self.pos..self.pos + write_size].copy_from_slice(src);
-            self.pos += write_size;
-            return write_size;
+        match self.try_write(src) {
+            Ok(()) => src.len(),
+            Err(_) => 0,
         }
-        0
     }
 
     pub fn write_str(&mut self, src: &str) -> usize {
-        let write_size = src.len();
-        if self.is_available(write_size) {
-            #[allow(clippy::useless_conversion)]
-            let result = src.as_bytes().try_into();
-            match result {
-                Ok(bytes) => {
-                    self.buf[self.pos..self.pos + write_size].copy_from_slice(bytes);
-                    self.pos += write_size;
-                    return write_size;
-                }
-                Err(error) => {
-                    return 0;
-                }
-            }
-        }
-        0
+        self.write(src.as_bytes())
     }
 
     pub fn write_str_with_zero_terminator(&mut self, src: &str) -> usize {
-        let write_size = src.len() + 1;
-        if self.is_available(write_size) {
-            self.write_str(src);
-            self.write_u8(0);
-            return write_size;
+        match self.try_write_str_with_zero_terminator(src) {
+            Ok(()) => src.len() + 1,
+            Err(_) => 0,
         }
-        0
     }
 }
 
 /// Access StreamBufWriter component by index
-impl<'a> Index<usize> for StreamBufWriter<'a> {
+impl<'a, E> Index<usize> for StreamBufWriter<'a, E> {
     type Output = u8;
     fn index(&self, index: usize) -> &u8 {
         &self.buf[index]
@@ -246,15 +758,15 @@ impl<'a> Index<usize> for StreamBufWriter<'a> {
 }
 
 /// Set StreamBufWriter component by index
-impl<'a> IndexMut<usize> for StreamBufWriter<'a> {
+impl<'a, E> IndexMut<usize> for StreamBufWriter<'a, E> {
     fn index_mut(&mut self, index: usize) -> &mut u8 {
         &mut self.buf[index]
     }
 }
 
-impl<'a> From<StreamBufWriter<'a>> for StreamBufReader<'a> {
-    fn from(sbuf: StreamBufWriter<'a>) -> Self {
-        Self::new(&sbuf.buf[..sbuf.pos()])
+impl<'a, E: ByteOrder> From<StreamBufWriter<'a, E>> for StreamBufReader<'a, E> {
+    fn from(sbuf: StreamBufWriter<'a, E>) -> Self {
+        Self::with_byte_order(&sbuf.buf[..sbuf.pos()], sbuf.byte_order)
         //Self::new(&sbuf.buf[..sbuf.pos()], sbuf.bytes_written())
         //Self::new(&sbuf.buf[..], sbuf.bytes_written())
     }
@@ -272,7 +784,6 @@ buf.write_u16(0x1234);
 #[cfg(any(debug_assertions, test))]
 mod tests {
     use super::*;
-    use vector_quaternion_matrix::Vector3d;
 
     #[test]
     fn new() {
@@ -555,4 +1066,209 @@ mod tests {
         sbuf_writer.write_f32(1234.56);
         assert_eq!([0xec, 0x51, 0x9a, 0x44], data[0..4]);
     }
+
+    #[test]
+    fn try_write() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(Ok(()), sbuf.try_write_u8(0x0a));
+        assert_eq!(Ok(()), sbuf.try_write_u16_big_endian(0x1b2c));
+        assert_eq!(Ok(()), sbuf.try_write_u32(0x605f4e3d));
+        assert!(!sbuf.overflowed());
+        assert_eq!(
+            Err(WriteError {
+                pos: 7,
+                requested: 4,
+                available: 1
+            }),
+            sbuf.try_write_f32(1.0)
+        );
+        assert!(sbuf.overflowed());
+        assert_eq!(7, sbuf.bytes_written());
+        assert_eq!(
+            Err(WriteError {
+                pos: 7,
+                requested: 3,
+                available: 1
+            }),
+            sbuf.try_write_str_with_zero_terminator("ab")
+        );
+        assert_eq!(7, sbuf.bytes_written());
+        // sizes near usize::MAX must not overflow
+        assert_eq!(
+            Err(WriteError {
+                pos: 7,
+                requested: usize::MAX,
+                available: 1
+            }),
+            sbuf.try_fill(0, usize::MAX)
+        );
+        assert!(sbuf.check_available(usize::MAX).is_err());
+        assert!(!sbuf.fill_without_advancing(0, usize::MAX));
+        let checkpoint = sbuf.checkpoint();
+        sbuf.advance(usize::MAX);
+        assert_eq!(8, sbuf.bytes_written());
+        sbuf.rollback(checkpoint);
+        assert_eq!(Ok(()), sbuf.try_fill(0xFF, 1));
+        assert!(sbuf.is_full());
+        // the overflow flag is sticky until reset
+        assert!(sbuf.overflowed());
+
+        let sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!([0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60, 0xFF], sbuf_reader.get_data());
+    }
+
+    #[test]
+    fn overflowed() {
+        let mut data = [0u8; 4];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u16(0x1234);
+        sbuf.write_str("abc");
+        assert!(sbuf.overflowed());
+        assert_eq!(2, sbuf.bytes_written());
+        sbuf.reset();
+        assert!(!sbuf.overflowed());
+        sbuf.fill(0, 5);
+        assert!(sbuf.overflowed());
+        sbuf.reset();
+        sbuf.write_u16_big_endian(0x1234);
+        sbuf.write_u16(0x5678);
+        assert!(!sbuf.overflowed());
+        sbuf.write_u8(0);
+        assert!(sbuf.overflowed());
+    }
+
+    #[test]
+    fn stream_buf_all_primitives() {
+        const BUF_SIZE: usize = 256;
+        let mut data = [0u8; BUF_SIZE];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+
+        sbuf.write_i8(-1);
+        sbuf.write_i16(-2);
+        sbuf.write_i32(-3);
+        sbuf.write_u64(0x0102_0304_0506_0708);
+        sbuf.write_i64(-4);
+        sbuf.write_u128(u128::MAX - 5);
+        sbuf.write_i128(i128::MIN);
+        sbuf.write_f64(1234.56);
+        sbuf.write_i16_big_endian(-7);
+        sbuf.write_i32_big_endian(-8);
+        sbuf.write_u64_big_endian(0x0102_0304_0506_0708);
+        sbuf.write_i64_big_endian(-9);
+        sbuf.write_u128_big_endian(10);
+        sbuf.write_i128_big_endian(-11);
+        sbuf.write_f32_big_endian(1234.56);
+        sbuf.write_f64_big_endian(-1234.56);
+        assert!(!sbuf.overflowed());
+        assert_eq!(
+            1 + 2 + 4 + 8 + 8 + 16 + 16 + 8 + 2 + 4 + 8 + 8 + 16 + 16 + 4 + 8,
+            sbuf.bytes_written()
+        );
+        assert_eq!([0x01, 0x02, 0x03, 0x04], sbuf.get_data()[69..73]);
+        assert_eq!([0x44, 0x9a, 0x51, 0xec], sbuf.get_data()[117..121]);
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(-1, sbuf_reader.read_i8());
+        assert_eq!(-2, sbuf_reader.read_i16());
+        assert_eq!(-3, sbuf_reader.read_i32());
+        assert_eq!(0x0102_0304_0506_0708, sbuf_reader.read_u64());
+        assert_eq!(-4, sbuf_reader.read_i64());
+        assert_eq!(u128::MAX - 5, sbuf_reader.read_u128());
+        assert_eq!(i128::MIN, sbuf_reader.read_i128());
+        assert_eq!(1234.56, sbuf_reader.read_f64());
+        assert_eq!(-7, sbuf_reader.read_i16_big_endian());
+        assert_eq!(-8, sbuf_reader.read_i32_big_endian());
+        assert_eq!(0x0102_0304_0506_0708, sbuf_reader.read_u64_big_endian());
+        assert_eq!(-9, sbuf_reader.read_i64_big_endian());
+        assert_eq!(10, sbuf_reader.read_u128_big_endian());
+        assert_eq!(-11, sbuf_reader.read_i128_big_endian());
+        assert_eq!(1234.56, sbuf_reader.read_f32_big_endian());
+        assert_eq!(-1234.56, sbuf_reader.read_f64_big_endian());
+        assert_eq!(0, sbuf_reader.bytes_remaining());
+    }
+
+    #[test]
+    fn write_primitive_overflow() {
+        let mut data = [0u8; 7];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_i16_big_endian(-1);
+        sbuf.write_f64(1.0);
+        assert!(sbuf.overflowed());
+        assert_eq!(2, sbuf.bytes_written());
+        assert_eq!(
+            Err(WriteError {
+                pos: 2,
+                requested: 8,
+                available: 5
+            }),
+            sbuf.try_write_u64_big_endian(1)
+        );
+        assert_eq!(Ok(()), sbuf.try_write_i32(-1));
+        assert_eq!(6, sbuf.bytes_written());
+    }
+
+    #[test]
+    fn checkpoint_rollback() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u8(1);
+        let outer = sbuf.checkpoint();
+        assert_eq!(1, outer.pos());
+        sbuf.write_u16(2);
+        let inner = sbuf.checkpoint();
+        sbuf.write_u32(3);
+        sbuf.write_u32(4);
+        assert!(sbuf.overflowed());
+        sbuf.rollback(inner);
+        assert!(!sbuf.overflowed());
+        assert_eq!(3, sbuf.bytes_written());
+        sbuf.rollback(outer);
+        assert_eq!(1, sbuf.bytes_written());
+
+        // a checkpoint beyond the current position is ignored
+        sbuf.reset();
+        sbuf.rollback(inner);
+        assert_eq!(0, sbuf.bytes_written());
+    }
+
+    #[test]
+    fn write_at() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u32(0);
+        sbuf.write_u16_at(1, 0x0a1b);
+        assert_eq!(Ok(()), sbuf.try_write_u8_at(3, 0x2c));
+        assert!(!sbuf.overflowed());
+        assert_eq!(
+            Err(WriteError {
+                pos: 3,
+                requested: 2,
+                available: 1
+            }),
+            sbuf.try_write_i16_at(3, -1)
+        );
+        assert_eq!(
+            Some(WriteError {
+                pos: 3,
+                requested: 2,
+                available: 1
+            }),
+            sbuf.overflow_error()
+        );
+        sbuf.write_f32_at(8, 1.0);
+        // an offset near usize::MAX must not overflow
+        assert_eq!(
+            Err(WriteError {
+                pos: usize::MAX,
+                requested: 2,
+                available: 0
+            }),
+            sbuf.try_write_u16_at(usize::MAX, 0x3d4e)
+        );
+        assert!(sbuf.try_write_u8_at(usize::MAX - 1, 0x3d).is_err());
+        assert!(sbuf.put_at(usize::MAX, &0x3d4eu16).is_err());
+        assert_eq!(4, sbuf.bytes_written());
+        assert_eq!([0x00, 0x1b, 0x0a, 0x2c], sbuf.get_ref());
+    }
 }
@@ -0,0 +1,112 @@
+#![allow(unused)]
+
+use crate::byte_order::{ByteOrder, LittleEndian};
+use crate::stream_buf_writer::{Checkpoint, StreamBufWriter};
+use core::ops::{Deref, DerefMut};
+
+/// Guard returned by `StreamBufWriter::transaction()`.
+///
+/// Writes made through the guard are rolled back when it is dropped, unless `commit()` has been called,
+/// so a partially written record never leaks into the transmitted data.
+/// ```
+/// # use stream_buf::{StreamBufWriter, WriteError};
+/// fn write_record(sbuf_writer: &mut StreamBufWriter<'_>, values: &[u16]) -> Result<(), WriteError> {
+///     let mut transaction = sbuf_writer.transaction();
+///     transaction.try_write_u8(values.len() as u8)?;
+///     for value in values {
+///         transaction.try_write_u16(*value)?;
+///     }
+///     transaction.commit();
+///     Ok(())
+/// }
+///
+/// let mut data = [0u8; 8];
+/// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+///
+/// assert!(write_record(&mut sbuf_writer, &[1, 2]).is_ok());
+/// assert!(write_record(&mut sbuf_writer, &[3, 4]).is_err());
+/// assert_eq!([0x02, 0x01, 0x00, 0x02, 0x00], sbuf_writer.get_ref());
+/// assert!(!sbuf_writer.overflowed());
+/// ```
+pub struct Transaction<'w, 'a, E: ByteOrder = LittleEndian> {
+    writer: &'w mut StreamBufWriter<'a, E>,
+    checkpoint: Checkpoint,
+    committed: bool,
+}
+
+impl<'w, 'a, E: ByteOrder> Transaction<'w, 'a, E> {
+    pub(crate) fn new(writer: &'w mut StreamBufWriter<'a, E>) -> Self {
+        let checkpoint = writer.checkpoint();
+        Self {
+            writer,
+            checkpoint,
+            committed: false,
+        }
+    }
+
+    /// Keep everything written through the guard.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Discard everything written through the guard. This is what happens if the guard is dropped without committing.
+    pub fn rollback(self) {}
+}
+
+impl<'w, 'a, E: ByteOrder> Deref for Transaction<'w, 'a, E> {
+    type Target = StreamBufWriter<'a, E>;
+    fn deref(&self) -> &Self::Target {
+        self.writer
+    }
+}
+
+impl<'w, 'a, E: ByteOrder> DerefMut for Transaction<'w, 'a, E> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.writer
+    }
+}
+
+impl<'w, 'a, E: ByteOrder> Drop for Transaction<'w, 'a, E> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.writer.rollback(self.checkpoint);
+        }
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction() {
+        let mut data = [0u8; 8];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_u8(1);
+        {
+            let mut transaction = sbuf.transaction();
+            transaction.write_u16(2);
+            transaction.commit();
+        }
+        assert_eq!(3, sbuf.bytes_written());
+        {
+            let mut transaction = sbuf.transaction();
+            transaction.write_u32(3);
+            assert_eq!(7, transaction.bytes_written());
+        }
+        assert_eq!(3, sbuf.bytes_written());
+
+        // nested transactions
+        let mut outer = sbuf.transaction();
+        outer.write_u8(4);
+        let mut inner = outer.transaction();
+        inner.write_u32(5);
+        inner.write_u8(6);
+        assert!(inner.overflowed());
+        inner.rollback();
+        assert!(!outer.overflowed());
+        assert_eq!(4, outer.bytes_written());
+        outer.commit();
+        assert_eq!([1, 2, 0, 4], sbuf.get_ref());
+    }
+}
@@ -0,0 +1,279 @@
+#![allow(unused)]
+
+//! LEB128 variable length integers, with zigzag encoding for signed values.
+//!
+//! Each byte holds seven bits of the value, least significant group first, with the top bit set on all but the last
+//! byte. Values below 128 take a single byte, so streams dominated by small values are much smaller than with
+//! fixed-width fields. Signed values are zigzag encoded, so small negative values are also small.
+//! ```
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! let mut data = [0u8; 8];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//!
+//! sbuf_writer.write_varint_u32(300);
+//! sbuf_writer.write_varint_i32(-3);
+//! assert_eq!([0xac, 0x02, 0x05], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! assert_eq!(300, sbuf_reader.read_varint_u32());
+//! assert_eq!(-3, sbuf_reader.read_varint_i32());
+//! ```
+
+use crate::byte_order::ByteOrder;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+
+/// Map signed values to unsigned values so that values of small magnitude have small encodings:
+/// 0, -1, 1, -2, 2 ... map to 0, 1, 2, 3, 4 ...
+pub(crate) fn zigzag_encode_32(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+pub(crate) fn zigzag_decode_32(value: u32) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+pub(crate) fn zigzag_encode_64(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub(crate) fn zigzag_decode_64(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    fn try_write_varint(&mut self, mut value: u64) -> Result<(), WriteError> {
+        let mut bytes = [0u8; 10];
+        let mut len = 0;
+        for byte in &mut bytes {
+            len += 1;
+            *byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                break;
+            }
+            *byte |= 0x80;
+        }
+        self.try_write(&bytes[..len])
+    }
+
+    /// Write a u32 as a LEB128 varint of 1 to 5 bytes, or return a WriteError if there is not enough space available.
+    pub fn try_write_varint_u32(&mut self, value: u32) -> Result<(), WriteError> {
+        self.try_write_varint(u64::from(value))
+    }
+
+    /// Write a u64 as a LEB128 varint of 1 to 10 bytes, or return a WriteError if there is not enough space available.
+    pub fn try_write_varint_u64(&mut self, value: u64) -> Result<(), WriteError> {
+        self.try_write_varint(value)
+    }
+
+    /// Write an i32 as a zigzag encoded LEB128 varint of 1 to 5 bytes,
+    /// or return a WriteError if there is not enough space available.
+    pub fn try_write_varint_i32(&mut self, value: i32) -> Result<(), WriteError> {
+        self.try_write_varint(u64::from(zigzag_encode_32(value)))
+    }
+
+    /// Write an i64 as a zigzag encoded LEB128 varint of 1 to 10 bytes,
+    /// or return a WriteError if there is not enough space available.
+    pub fn try_write_varint_i64(&mut self, value: i64) -> Result<(), WriteError> {
+        self.try_write_varint(zigzag_encode_64(value))
+    }
+
+    /// Write a u32 as a LEB128 varint of 1 to 5 bytes.
+    pub fn write_varint_u32(&mut self, value: u32) {
+        let _ = self.try_write_varint_u32(value);
+    }
+
+    /// Write a u64 as a LEB128 varint of 1 to 10 bytes.
+    pub fn write_varint_u64(&mut self, value: u64) {
+        let _ = self.try_write_varint_u64(value);
+    }
+
+    /// Write an i32 as a zigzag encoded LEB128 varint of 1 to 5 bytes.
+    pub fn write_varint_i32(&mut self, value: i32) {
+        let _ = self.try_write_varint_i32(value);
+    }
+
+    /// Write an i64 as a zigzag encoded LEB128 varint of 1 to 10 bytes.
+    pub fn write_varint_i64(&mut self, value: i64) {
+        let _ = self.try_write_varint_i64(value);
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Read a LEB128 varint holding at most `bits` bits.
+    /// Returns `ReadError::Invalid` at the offending byte for an overlong encoding, that is one with redundant
+    /// trailing zero bytes, or for a value that does not fit in `bits` bits. On error the position is left unchanged.
+    fn try_read_varint(&mut self, bits: u32) -> Result<u64, ReadError> {
+        let bytes = self.remaining_slice();
+        let mut value = 0u64;
+        for (index, byte) in bytes.iter().enumerate() {
+            let pos = self.pos() + index;
+            let shift = 7 * index as u32;
+            if shift >= bits {
+                return Err(ReadError::Invalid { pos });
+            }
+            let group = u64::from(byte & 0x7f);
+            if shift + 7 > bits && group >> (bits - shift) != 0 {
+                return Err(ReadError::Invalid { pos });
+            }
+            value |= group << shift;
+            if byte & 0x80 == 0 {
+                if *byte == 0 && index > 0 {
+                    return Err(ReadError::Invalid { pos });
+                }
+                self.advance(index + 1);
+                return Ok(value);
+            }
+        }
+        Err(ReadError::Truncated {
+            pos: self.pos(),
+            requested: bytes.len() + 1,
+            available: bytes.len(),
+        })
+    }
+
+    /// Return a u32 read from a LEB128 varint, or a ReadError if the varint is truncated, overlong or too large.
+    /// ```
+    /// # use stream_buf::{ReadError, StreamBufReader};
+    /// let buf = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x80, 0x00, 0xff, 0xff, 0xff, 0xff, 0x1f];
+    /// let mut sbuf_reader = StreamBufReader::new(&buf);
+    ///
+    /// assert_eq!(Ok(u32::MAX), sbuf_reader.try_read_varint_u32());
+    /// // zero encoded in two bytes is overlong
+    /// assert_eq!(Err(ReadError::Invalid { pos: 6 }), sbuf_reader.try_read_varint_u32());
+    /// sbuf_reader.advance(2);
+    /// // 2^33 - 1 does not fit in a u32
+    /// assert_eq!(Err(ReadError::Invalid { pos: 11 }), sbuf_reader.try_read_varint_u32());
+    /// assert_eq!(Ok((1 << 33) - 1), sbuf_reader.try_read_varint_u64());
+    /// ```
+    pub fn try_read_varint_u32(&mut self) -> Result<u32, ReadError> {
+        self.try_read_varint(u32::BITS).map(|value| value as u32)
+    }
+
+    /// Return a u64 read from a LEB128 varint, or a ReadError if the varint is truncated, overlong or too large.
+    pub fn try_read_varint_u64(&mut self) -> Result<u64, ReadError> {
+        self.try_read_varint(u64::BITS)
+    }
+
+    /// Return an i32 read from a zigzag encoded LEB128 varint,
+    /// or a ReadError if the varint is truncated, overlong or too large.
+    pub fn try_read_varint_i32(&mut self) -> Result<i32, ReadError> {
+        self.try_read_varint_u32().map(zigzag_decode_32)
+    }
+
+    /// Return an i64 read from a zigzag encoded LEB128 varint,
+    /// or a ReadError if the varint is truncated, overlong or too large.
+    pub fn try_read_varint_i64(&mut self) -> Result<i64, ReadError> {
+        self.try_read_varint_u64().map(zigzag_decode_64)
+    }
+
+    /// Return a u32 read from a LEB128 varint, or 0 if the varint is truncated, overlong or too large.
+    pub fn read_varint_u32(&mut self) -> u32 {
+        self.try_read_varint_u32().unwrap_or_default()
+    }
+
+    /// Return a u64 read from a LEB128 varint, or 0 if the varint is truncated, overlong or too large.
+    pub fn read_varint_u64(&mut self) -> u64 {
+        self.try_read_varint_u64().unwrap_or_default()
+    }
+
+    /// Return an i32 read from a zigzag encoded LEB128 varint, or 0 if the varint is truncated, overlong or too large.
+    pub fn read_varint_i32(&mut self) -> i32 {
+        self.try_read_varint_i32().unwrap_or_default()
+    }
+
+    /// Return an i64 read from a zigzag encoded LEB128 varint, or 0 if the varint is truncated, overlong or too large.
+    pub fn read_varint_i64(&mut self) -> i64 {
+        self.try_read_varint_i64().unwrap_or_default()
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zigzag() {
+        assert_eq!(0, zigzag_encode_32(0));
+        assert_eq!(1, zigzag_encode_32(-1));
+        assert_eq!(2, zigzag_encode_32(1));
+        assert_eq!(u32::MAX - 1, zigzag_encode_32(i32::MAX));
+        assert_eq!(u32::MAX, zigzag_encode_32(i32::MIN));
+        assert_eq!(u64::MAX, zigzag_encode_64(i64::MIN));
+        for value in [0, -1, 1, -64, 64, i32::MIN, i32::MAX] {
+            assert_eq!(value, zigzag_decode_32(zigzag_encode_32(value)));
+            assert_eq!(i64::from(value), zigzag_decode_64(zigzag_encode_64(i64::from(value))));
+        }
+    }
+
+    #[test]
+    fn write_varint() {
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_varint_u32(0);
+        sbuf.write_varint_u32(127);
+        sbuf.write_varint_u32(128);
+        sbuf.write_varint_u32(u32::MAX);
+        sbuf.write_varint_i32(-64);
+        sbuf.write_varint_i32(64);
+        sbuf.write_varint_u64(u64::MAX);
+        assert_eq!(
+            [
+                0x00, 0x7f, 0x80, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, 0x80, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff, 0xff, 0xff, 0x01
+            ],
+            sbuf.get_ref()
+        );
+
+        // a varint that does not fit is not partially written
+        let mut data = [0u8; 2];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        assert_eq!(
+            Err(WriteError {
+                pos: 0,
+                requested: 3,
+                available: 2
+            }),
+            sbuf.try_write_varint_i64(-65536)
+        );
+        assert_eq!(0, sbuf.bytes_written());
+    }
+
+    #[test]
+    fn read_varint() {
+        let buf = [
+            0x00, 0x7f, 0x80, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, 0x80, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0x01, 0x80,
+        ];
+        let mut sbuf = StreamBufReader::new(&buf);
+        assert_eq!(0, sbuf.read_varint_u32());
+        assert_eq!(127, sbuf.read_varint_u32());
+        assert_eq!(128, sbuf.read_varint_u32());
+        assert_eq!(u32::MAX, sbuf.read_varint_u32());
+        assert_eq!(-64, sbuf.read_varint_i32());
+        assert_eq!(64, sbuf.read_varint_i64());
+        // a 10 byte varint overflows a u32 at its fifth byte
+        assert_eq!(Err(ReadError::Invalid { pos: 16 }), sbuf.try_read_varint_u32());
+        assert_eq!(0, sbuf.read_varint_i32());
+        assert_eq!(12, sbuf.pos());
+        assert_eq!(i64::MIN, sbuf.read_varint_i64());
+        assert_eq!(
+            Err(ReadError::Truncated {
+                pos: 22,
+                requested: 2,
+                available: 1
+            }),
+            sbuf.try_read_varint_u64()
+        );
+        assert_eq!(22, sbuf.pos());
+
+        // a tenth byte greater than 1 overflows a u64, as does an eleventh byte
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x02];
+        let mut sbuf = StreamBufReader::new(&buf);
+        assert_eq!(Err(ReadError::Invalid { pos: 9 }), sbuf.try_read_varint_u64());
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        let mut sbuf = StreamBufReader::new(&buf);
+        assert_eq!(Err(ReadError::Invalid { pos: 10 }), sbuf.try_read_varint_u64());
+    }
+}
@@ -0,0 +1,580 @@
+#![allow(unused)]
+
+//! Reading and writing the vector, quaternion and matrix types of the `vector_quaternion_matrix` crate, enabled by
+//! the `vqm` feature.
+//!
+//! Each type can be written as f32 components in the byte order of the stream_buf, as big endian f32 components, or
+//! as i16 components scaled by a given factor, which halves the size at the cost of range and precision. Scaled
+//! components are rounded to the nearest integer and saturated to the range of an i16.
+//!
+//! Vectors are written x, y, z. Quaternions are written w, x, y, z. Matrices are written in the order of their
+//! elements, row by row.
+//!
+//! Quaternions can also be written compressed, see `StreamBufWriter::write_quaternion_compressed()`.
+//! ```
+//! # use stream_buf::{StreamBufReader, StreamBufWriter};
+//! # use vector_quaternion_matrix::Vector3d;
+//! let mut data = [0u8; 16];
+//! let mut sbuf_writer = StreamBufWriter::new(&mut data);
+//!
+//! // gyro rate in degrees per second, scaled by 16.4 as by an MPU6000, so z saturates
+//! let gyro = Vector3d { x: 100.0, y: -2.5, z: 2500.0 };
+//! sbuf_writer.write_vector3d_as_i16_scaled(&gyro, 16.4);
+//! assert_eq!([0x68, 0x06, 0xd7, 0xff, 0xff, 0x7f], sbuf_writer.get_ref());
+//!
+//! let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+//! let gyro = sbuf_reader.read_vector3d_from_i16_scaled(16.4);
+//! assert!((gyro.y + 2.5).abs() < 0.5 / 16.4);
+//! ```
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::{BitOrder, BitWriter, bit_mask};
+use crate::byte_order::ByteOrder;
+use crate::encode::{Decode, Encode};
+use crate::fixed_point::scale_to_i16;
+use crate::stream_buf_reader::{ReadError, StreamBufReader};
+use crate::stream_buf_writer::{StreamBufWriter, WriteError};
+use core::f32::consts::{FRAC_1_SQRT_2, SQRT_2};
+use vector_quaternion_matrix::{Matrix3x3, Quaternion, Vector3d};
+
+/// Smallest and largest number of bits per component of a compressed quaternion.
+const QUATERNION_COMPRESSED_BITS_MIN: u32 = 2;
+const QUATERNION_COMPRESSED_BITS_MAX: u32 = 20;
+/// Number of bits used for the index of the dropped component of a compressed quaternion.
+const QUATERNION_COMPRESSED_INDEX_BITS: u32 = 2;
+
+fn vector3d_to_array(v: &Vector3d) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn vector3d_from_array([x, y, z]: [f32; 3]) -> Vector3d {
+    Vector3d { x, y, z }
+}
+
+fn quaternion_to_array(q: &Quaternion) -> [f32; 4] {
+    [q.w, q.x, q.y, q.z]
+}
+
+fn quaternion_from_array([w, x, y, z]: [f32; 4]) -> Quaternion {
+    Quaternion { w, x, y, z }
+}
+
+fn matrix3x3_to_array(m: &Matrix3x3) -> [f32; 9] {
+    core::array::from_fn(|index| m[index])
+}
+
+fn matrix3x3_from_array(a: [f32; 9]) -> Matrix3x3 {
+    let mut m = Matrix3x3::default();
+    for (index, value) in a.into_iter().enumerate() {
+        m[index] = value;
+    }
+    m
+}
+
+/// Generate the `try_write_*` functions and their infallible `write_*` counterparts for a vqm type.
+macro_rules! write_vqm {
+    (
+        $type:ty,
+        $to_array:ident,
+        $try_write:ident,
+        $write:ident,
+        $try_write_big_endian:ident,
+        $write_big_endian:ident,
+        $try_write_scaled:ident,
+        $write_scaled:ident
+    ) => {
+        #[doc = concat!("Write ", stringify!($type), " as f32 components in the stream_buf's byte order,")]
+        #[doc = "or return a WriteError if there is not enough space available. On error nothing is written."]
+        pub fn $try_write(&mut self, value: &$type) -> Result<(), WriteError> {
+            self.try_write_f32_components(&$to_array(value), false)
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " as f32 components in the stream_buf's byte order.")]
+        #[doc = "The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write(&mut self, value: &$type) {
+            let _ = self.$try_write(value);
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " as big endian f32 components,")]
+        #[doc = "or return a WriteError if there is not enough space available. On error nothing is written."]
+        pub fn $try_write_big_endian(&mut self, value: &$type) -> Result<(), WriteError> {
+            self.try_write_f32_components(&$to_array(value), true)
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " as big endian f32 components.")]
+        #[doc = "The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write_big_endian(&mut self, value: &$type) {
+            let _ = self.$try_write_big_endian(value);
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " as i16 components, multiplied by `scale`, in the stream_buf's")]
+        #[doc = "byte order, or return a WriteError if there is not enough space available."]
+        #[doc = "On error nothing is written."]
+        pub fn $try_write_scaled(&mut self, value: &$type, scale: f32) -> Result<(), WriteError> {
+            self.try_write_i16_scaled_components(&$to_array(value), scale)
+        }
+
+        #[doc = concat!("Write ", stringify!($type), " as i16 components, multiplied by `scale`, in the stream_buf's")]
+        #[doc = "byte order. The value is dropped and the overflow flag set if there is not enough space available."]
+        pub fn $write_scaled(&mut self, value: &$type, scale: f32) {
+            let _ = self.$try_write_scaled(value, scale);
+        }
+    };
+}
+
+/// Generate the `try_read_*` functions and their infallible `read_*` counterparts for a vqm type.
+macro_rules! read_vqm {
+    (
+        $type:ty,
+        $from_array:ident,
+        $try_read:ident,
+        $read:ident,
+        $try_read_big_endian:ident,
+        $read_big_endian:ident,
+        $try_read_scaled:ident,
+        $read_scaled:ident
+    ) => {
+        #[doc = concat!("Return ", stringify!($type), " read as f32 components in the stream_buf's byte order,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read(&mut self) -> Result<$type, ReadError> {
+            self.try_read_f32_components(false).map($from_array)
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read as f32 components in the stream_buf's byte order,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $read(&mut self) -> $type {
+            self.$try_read().unwrap_or_default()
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read as big endian f32 components,")]
+        #[doc = "or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read_big_endian(&mut self) -> Result<$type, ReadError> {
+            self.try_read_f32_components(true).map($from_array)
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read as big endian f32 components,")]
+        #[doc = "or zero if there are not enough bytes remaining."]
+        pub fn $read_big_endian(&mut self) -> $type {
+            self.$try_read_big_endian().unwrap_or_default()
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read as i16 components in the stream_buf's byte order and")]
+        #[doc = "divided by `scale`, or a ReadError if there are not enough bytes remaining."]
+        pub fn $try_read_scaled(&mut self, scale: f32) -> Result<$type, ReadError> {
+            self.try_read_i16_scaled_components(scale).map($from_array)
+        }
+
+        #[doc = concat!("Return ", stringify!($type), " read as i16 components in the stream_buf's byte order and")]
+        #[doc = "divided by `scale`, or zero if there are not enough bytes remaining."]
+        pub fn $read_scaled(&mut self, scale: f32) -> $type {
+            self.$try_read_scaled(scale).unwrap_or_default()
+        }
+    };
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    fn try_write_f32_components<const N: usize>(
+        &mut self,
+        values: &[f32; N],
+        big_endian: bool,
+    ) -> Result<(), WriteError> {
+        self.check_available(N * size_of::<f32>())?;
+        for value in values {
+            if big_endian {
+                self.try_write_f32_big_endian(*value)?;
+            } else {
+                self.try_write_f32(*value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn try_write_i16_scaled_components<const N: usize>(
+        &mut self,
+        values: &[f32; N],
+        scale: f32,
+    ) -> Result<(), WriteError> {
+        self.check_available(N * size_of::<i16>())?;
+        for value in values {
+            self.try_write_i16(scale_to_i16(*value, scale))?;
+        }
+        Ok(())
+    }
+
+    write_vqm!(
+        Vector3d,
+        vector3d_to_array,
+        try_write_vector3d,
+        write_vector3d,
+        try_write_vector3d_big_endian,
+        write_vector3d_big_endian,
+        try_write_vector3d_as_i16_scaled,
+        write_vector3d_as_i16_scaled
+    );
+    write_vqm!(
+        Quaternion,
+        quaternion_to_array,
+        try_write_quaternion,
+        write_quaternion,
+        try_write_quaternion_big_endian,
+        write_quaternion_big_endian,
+        try_write_quaternion_as_i16_scaled,
+        write_quaternion_as_i16_scaled
+    );
+    write_vqm!(
+        Matrix3x3,
+        matrix3x3_to_array,
+        try_write_matrix3x3,
+        write_matrix3x3,
+        try_write_matrix3x3_big_endian,
+        write_matrix3x3_big_endian,
+        try_write_matrix3x3_as_i16_scaled,
+        write_matrix3x3_as_i16_scaled
+    );
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    fn try_read_f32_components<const N: usize>(&mut self, big_endian: bool) -> Result<[f32; N], ReadError> {
+        self.check_remaining(N * size_of::<f32>())?;
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = if big_endian {
+                self.try_read_f32_big_endian()?
+            } else {
+                self.try_read_f32()?
+            };
+        }
+        Ok(values)
+    }
+
+    fn try_read_i16_scaled_components<const N: usize>(&mut self, scale: f32) -> Result<[f32; N], ReadError> {
+        self.check_remaining(N * size_of::<i16>())?;
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = f32::from(self.try_read_i16()?) / scale;
+        }
+        Ok(values)
+    }
+
+    read_vqm!(
+        Vector3d,
+        vector3d_from_array,
+        try_read_vector3d,
+        read_vector3d,
+        try_read_vector3d_big_endian,
+        read_vector3d_big_endian,
+        try_read_vector3d_from_i16_scaled,
+        read_vector3d_from_i16_scaled
+    );
+    read_vqm!(
+        Quaternion,
+        quaternion_from_array,
+        try_read_quaternion,
+        read_quaternion,
+        try_read_quaternion_big_endian,
+        read_quaternion_big_endian,
+        try_read_quaternion_from_i16_scaled,
+        read_quaternion_from_i16_scaled
+    );
+    read_vqm!(
+        Matrix3x3,
+        matrix3x3_from_array,
+        try_read_matrix3x3,
+        read_matrix3x3,
+        try_read_matrix3x3_big_endian,
+        read_matrix3x3_big_endian,
+        try_read_matrix3x3_from_i16_scaled,
+        read_matrix3x3_from_i16_scaled
+    );
+}
+
+/// Generate `Encode` and `Decode` for a vqm type, as f32 components in the stream_buf's byte order.
+macro_rules! impl_encode_decode_vqm {
+    ($type:ty, $try_write:ident, $try_read:ident) => {
+        impl Encode for $type {
+            fn encode<E: ByteOrder>(&self, writer: &mut StreamBufWriter<'_, E>) -> Result<(), WriteError> {
+                writer.$try_write(self)
+            }
+        }
+
+        impl Decode for $type {
+            fn decode<E: ByteOrder>(reader: &mut StreamBufReader<'_, E>) -> Result<Self, ReadError> {
+                reader.$try_read()
+            }
+        }
+    };
+}
+
+impl_encode_decode_vqm!(Vector3d, try_write_vector3d, try_read_vector3d);
+impl_encode_decode_vqm!(Quaternion, try_write_quaternion, try_read_quaternion);
+impl_encode_decode_vqm!(Matrix3x3, try_write_matrix3x3, try_read_matrix3x3);
+
+/// Return the number of bytes of a quaternion compressed with `bits` per component.
+fn quaternion_compressed_size(bits: u32) -> usize {
+    (QUATERNION_COMPRESSED_INDEX_BITS + 3 * bits).div_ceil(8) as usize
+}
+
+/// Return the largest magnitude a component can be quantized to with `bits` per component.
+/// Zero is quantized exactly, so the range is symmetric and the lowest code is unused.
+fn quaternion_compressed_range(bits: u32) -> f32 {
+    bit_mask(bits - 1) as f32
+}
+
+/// Return the index of the largest component of the normalized `q`, and the other three components quantized to
+/// `bits` each, with the sign chosen so that the largest component is positive.
+fn compress_quaternion(q: &Quaternion, bits: u32) -> (u32, [u32; 3]) {
+    let mut components = quaternion_to_array(q);
+    let norm = libm::sqrtf(components.iter().map(|c| c * c).sum());
+    let mut largest = 0;
+    for index in 1..components.len() {
+        if components[index].abs() > components[largest].abs() {
+            largest = index;
+        }
+    }
+    // q and -q are the same rotation, so make the largest component positive and drop it
+    let scale = if components[largest] < 0.0 { -norm } else { norm };
+    if scale != 0.0 {
+        for component in &mut components {
+            *component /= scale;
+        }
+    }
+
+    let range = quaternion_compressed_range(bits);
+    let mut codes = [0u32; 3];
+    let others = (0..components.len()).filter(|index| *index != largest);
+    for (code, index) in codes.iter_mut().zip(others) {
+        // the smallest three components are in the range [-1/sqrt(2), 1/sqrt(2)]
+        let quantized = libm::roundf(components[index] * SQRT_2 * range).clamp(-range, range);
+        *code = (quantized as i32 + range as i32 + 1) as u32;
+    }
+    (largest as u32, codes)
+}
+
+/// Return the quaternion compressed by `compress_quaternion()`, with the dropped component recalculated from the
+/// other three.
+fn decompress_quaternion(largest: u32, codes: [u32; 3], bits: u32) -> Quaternion {
+    let range = quaternion_compressed_range(bits);
+    let largest = largest as usize;
+    let mut components = [0.0f32; 4];
+    let others = (0..components.len()).filter(|index| *index != largest);
+    for (code, index) in codes.into_iter().zip(others) {
+        components[index] = (code as i32 - range as i32 - 1) as f32 / range * FRAC_1_SQRT_2;
+    }
+    let sum_of_squares: f32 = components.iter().map(|c| c * c).sum();
+    components[largest] = libm::sqrtf((1.0 - sum_of_squares).max(0.0));
+    quaternion_from_array(components)
+}
+
+impl<'a, E: ByteOrder> StreamBufWriter<'a, E> {
+    /// Write the quaternion `q` compressed using the "smallest three" method, or return a WriteError if there is not
+    /// enough space available. On error nothing is written.
+    ///
+    /// `q` is normalized, its largest component is dropped, since it can be recalculated from the others, and the
+    /// other three are quantized to `bits` each. The index of the dropped component is written in 2 bits, followed by
+    /// the three quantized components, most significant bit first, padded with zeros to a whole number of bytes.
+    /// `bits` is clamped to the range 2 to 20.
+    ///
+    /// The worst-case angular error of the decompressed rotation is sqrt(6) / (2^(bits - 1) - 1) radians, that is:
+    /// * 10 bits: 4 bytes, 0.275 degrees
+    /// * 15 bits: 6 bytes, 0.0086 degrees
+    /// * 20 bits: 8 bytes, 0.00027 degrees
+    /// ```
+    /// # use stream_buf::{StreamBufReader, StreamBufWriter};
+    /// # use vector_quaternion_matrix::Quaternion;
+    /// let mut data = [0u8; 16];
+    /// let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    ///
+    /// let identity = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+    /// sbuf_writer.write_quaternion_compressed(&identity, 10);
+    /// assert_eq!([0x20, 0x08, 0x02, 0x00], sbuf_writer.get_ref());
+    ///
+    /// let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    /// assert_eq!(identity, sbuf_reader.read_quaternion_compressed(10));
+    /// ```
+    pub fn try_write_quaternion_compressed(&mut self, q: &Quaternion, bits: u32) -> Result<(), WriteError> {
+        let bits = bits.clamp(QUATERNION_COMPRESSED_BITS_MIN, QUATERNION_COMPRESSED_BITS_MAX);
+        self.check_available(quaternion_compressed_size(bits))?;
+        let (largest, codes) = compress_quaternion(q, bits);
+        let mut bit_writer = BitWriter::new(self, BitOrder::MsbFirst);
+        bit_writer.try_write_bits(largest, QUATERNION_COMPRESSED_INDEX_BITS)?;
+        for code in codes {
+            bit_writer.try_write_bits(code, bits)?;
+        }
+        bit_writer.try_flush()
+    }
+
+    /// Write the quaternion `q` compressed using the "smallest three" method, with `bits` per component.
+    /// The value is dropped and the overflow flag set if there is not enough space available.
+    pub fn write_quaternion_compressed(&mut self, q: &Quaternion, bits: u32) {
+        let _ = self.try_write_quaternion_compressed(q, bits);
+    }
+}
+
+impl<'a, E: ByteOrder> StreamBufReader<'a, E> {
+    /// Return the quaternion written by `write_quaternion_compressed()` with `bits` per component,
+    /// or a ReadError if there are not enough bytes remaining. On error nothing is consumed.
+    ///
+    /// The quaternion is normalized and its largest component is not negative.
+    pub fn try_read_quaternion_compressed(&mut self, bits: u32) -> Result<Quaternion, ReadError> {
+        let bits = bits.clamp(QUATERNION_COMPRESSED_BITS_MIN, QUATERNION_COMPRESSED_BITS_MAX);
+        self.check_remaining(quaternion_compressed_size(bits))?;
+        let mut bit_reader = BitReader::new(self, BitOrder::MsbFirst);
+        let largest = bit_reader.try_read_bits(QUATERNION_COMPRESSED_INDEX_BITS)?;
+        let mut codes = [0u32; 3];
+        for code in &mut codes {
+            *code = bit_reader.try_read_bits(bits)?;
+        }
+        Ok(decompress_quaternion(largest, codes, bits))
+    }
+
+    /// Return the quaternion written by `write_quaternion_compressed()` with `bits` per component,
+    /// or zero if there are not enough bytes remaining.
+    pub fn read_quaternion_compressed(&mut self, bits: u32) -> Quaternion {
+        self.try_read_quaternion_compressed(bits).unwrap_or_default()
+    }
+}
+
+#[cfg(any(debug_assertions, test))]
+mod tests {
+    use super::*;
+    use crate::byte_order::BigEndian;
+
+    #[test]
+    fn vector3d() {
+        let v = Vector3d {
+            x: 1.0,
+            y: -2.0,
+            z: 0.5,
+        };
+        let mut data = [0u8; 32];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_vector3d(&v);
+        sbuf.write_vector3d_big_endian(&v);
+        assert_eq!([0x00, 0x00, 0x80, 0x3f], sbuf.get_ref()[..4]);
+        assert_eq!([0x3f, 0x80, 0x00, 0x00], sbuf.get_ref()[12..16]);
+        assert_eq!(
+            Err(WriteError {
+                pos: 24,
+                requested: 12,
+                available: 8
+            }),
+            sbuf.try_write_vector3d(&v)
+        );
+        assert_eq!(24, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        assert_eq!(Ok(v), sbuf_reader.get::<Vector3d>());
+        assert_eq!(Ok(v), sbuf_reader.try_read_vector3d_big_endian());
+        assert!(sbuf_reader.try_read_vector3d().is_err());
+        assert_eq!(24, sbuf_reader.pos());
+    }
+
+    #[test]
+    fn quaternion_and_matrix() {
+        let q = Quaternion {
+            w: 0.5,
+            x: -0.5,
+            y: 0.5,
+            z: -0.5,
+        };
+        let m = matrix3x3_from_array([1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        let mut data = [0u8; 64];
+        let mut sbuf = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+        sbuf.write_quaternion(&q);
+        sbuf.write_matrix3x3_as_i16_scaled(&m, 1000.0);
+        sbuf.write_quaternion_as_i16_scaled(&q, 1.0e6);
+        assert_eq!([0x3f, 0x00, 0x00, 0x00, 0xbf, 0x00], sbuf.get_ref()[..6]);
+        assert_eq!([0x03, 0xe8, 0x07, 0xd0], sbuf.get_ref()[16..20]);
+        assert_eq!([0x7f, 0xff, 0x80, 0x00], sbuf.get_ref()[34..38]);
+        assert_eq!(42, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader<BigEndian> = sbuf.into();
+        assert_eq!(q, sbuf_reader.read_quaternion());
+        assert_eq!(
+            Ok([1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]),
+            sbuf_reader
+                .try_read_matrix3x3_from_i16_scaled(1000.0)
+                .map(|m| matrix3x3_to_array(&m))
+        );
+        let saturated = sbuf_reader.read_quaternion_from_i16_scaled(1.0);
+        assert_eq!([32767.0, -32768.0], [saturated.w, saturated.x]);
+    }
+
+    /// Return the angle, in radians, of the rotation between the unit quaternions `a` and `b`.
+    fn angle_between(a: &Quaternion, b: &Quaternion) -> f64 {
+        let a = quaternion_to_array(a).map(f64::from);
+        let b = quaternion_to_array(b).map(f64::from);
+        // sin of half the angle, by Lagrange's identity, which is accurate for small angles
+        let mut sin_squared = 0.0;
+        for i in 0..4 {
+            for j in i + 1..4 {
+                let cross = a[i] * b[j] - a[j] * b[i];
+                sin_squared += cross * cross;
+            }
+        }
+        2.0 * libm::asin(libm::sqrt(sin_squared).min(1.0))
+    }
+
+    #[test]
+    fn quaternion_compressed_error() -> Result<(), ReadError> {
+        let values = [-1.0, -0.5, -0.3, 0.0, 0.2, 0.5, 0.9];
+        for bits in [10, 15, 20] {
+            let bound = libm::sqrt(6.0) / (bit_mask(bits - 1) as f64);
+            let mut data = [0u8; 8];
+            for w in values {
+                for x in values {
+                    for y in values {
+                        for z in values {
+                            let norm = libm::sqrtf(w * w + x * x + y * y + z * z);
+                            if norm == 0.0 {
+                                continue;
+                            }
+                            let q = quaternion_from_array([w / norm, x / norm, y / norm, z / norm]);
+                            let mut sbuf = StreamBufWriter::new(&mut data);
+                            assert_eq!(Ok(()), sbuf.try_write_quaternion_compressed(&q, bits));
+                            assert_eq!(quaternion_compressed_size(bits), sbuf.bytes_written());
+                            let mut sbuf_reader: StreamBufReader = sbuf.into();
+                            let decompressed = sbuf_reader.try_read_quaternion_compressed(bits)?;
+                            assert!(angle_between(&q, &decompressed) <= bound * 1.01);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn quaternion_compressed() {
+        // largest component is negative z, so the quaternion is negated
+        let q = Quaternion {
+            w: 0.0,
+            x: 0.0,
+            y: 0.6,
+            z: -0.8,
+        };
+        let mut data = [0u8; 10];
+        let mut sbuf = StreamBufWriter::new(&mut data);
+        sbuf.write_quaternion_compressed(&q, 15);
+        assert_eq!(6, sbuf.bytes_written());
+        assert_eq!(0xc0, sbuf.get_ref()[0] & 0xc0);
+        assert_eq!(
+            Err(WriteError {
+                pos: 6,
+                requested: 6,
+                available: 4
+            }),
+            sbuf.try_write_quaternion_compressed(&q, 15)
+        );
+        sbuf.write_quaternion_compressed(&q, 1);
+        assert_eq!(7, sbuf.bytes_written());
+
+        let mut sbuf_reader: StreamBufReader = sbuf.into();
+        let decompressed = sbuf_reader.read_quaternion_compressed(15);
+        assert!((decompressed.y + 0.6).abs() < 1.0e-4);
+        assert!((decompressed.z - 0.8).abs() < 1.0e-4);
+        assert!(sbuf_reader.try_read_quaternion_compressed(15).is_err());
+        assert_eq!(6, sbuf_reader.pos());
+        assert_eq!(0.0, sbuf_reader.read_quaternion_compressed(2).w);
+    }
+}
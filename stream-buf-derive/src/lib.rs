@@ -0,0 +1,438 @@
+//! Derive macros for the `stream_buf::Encode` and `stream_buf::Decode` traits.
+//!
+//! `#[derive(StreamEncode, StreamDecode)]` serializes a struct field by field, in declaration order,
+//! through `StreamBufWriter` and `StreamBufReader`. Enums are serialized as a discriminant followed by the
+//! fields of the variant.
+//!
+//! Attributes are given as `#[stream_buf(...)]`:
+//!
+//! * container: `big_endian`, `little_endian` - default byte order for primitive fields and the enum discriminant,
+//!   if unspecified the byte order of the stream_buf is used. Other fields are serialized with their own `Encode` and
+//!   `Decode` implementations
+//! * enum: `discriminant = u8 | u16 | u32` - width of the discriminant (u8 if unspecified), the values are the
+//!   enum's own discriminants, which must be integer literals
+//! * field: `big_endian`, `little_endian` - byte order of a primitive field
+//! * field: `scale = 100.0, repr = i16` - float field sent as `round(value * scale)`, saturated to `repr`
+//! * field: `skip` - field is not serialized and is set to `Default::default()` when decoded
+#![deny(clippy::unwrap_used)]
+#![deny(clippy::expect_used)]
+#![deny(clippy::panic)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Error, Expr, ExprLit, Fields, GenericParam, Generics, Ident, Lit, Result,
+    Type, parse_macro_input, parse_quote,
+};
+
+const PRIMITIVES: [(&str, usize); 12] = [
+    ("u8", 1),
+    ("i8", 1),
+    ("u16", 2),
+    ("i16", 2),
+    ("u32", 4),
+    ("i32", 4),
+    ("u64", 8),
+    ("i64", 8),
+    ("u128", 16),
+    ("i128", 16),
+    ("f32", 4),
+    ("f64", 8),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Endian {
+    Little,
+    Big,
+}
+
+struct ContainerAttrs {
+    endian: Option<Endian>,
+    discriminant: Option<Type>,
+}
+
+struct FieldAttrs {
+    endian: Option<Endian>,
+    scale: Option<Expr>,
+    repr: Option<Type>,
+    skip: bool,
+}
+
+/// A field after attribute parsing, bound to a local variable called `binding`.
+struct FieldInfo {
+    member: Option<Ident>,
+    binding: Ident,
+    ty: Type,
+    endian: Option<Endian>,
+    scale: Option<(Expr, Type)>,
+    skip: bool,
+}
+
+#[proc_macro_derive(StreamEncode, attributes(stream_buf))]
+pub fn derive_stream_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(StreamDecode, attributes(stream_buf))]
+pub fn derive_stream_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn parse_container_attrs(attrs: &[Attribute]) -> Result<ContainerAttrs> {
+    let mut container = ContainerAttrs {
+        endian: None,
+        discriminant: None,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("stream_buf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("big_endian") {
+                container.endian = Some(Endian::Big);
+            } else if meta.path.is_ident("little_endian") {
+                container.endian = Some(Endian::Little);
+            } else if meta.path.is_ident("discriminant") {
+                container.discriminant = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown stream_buf container attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(container)
+}
+
+fn parse_field_attrs(attrs: &[Attribute]) -> Result<FieldAttrs> {
+    let mut field = FieldAttrs {
+        endian: None,
+        scale: None,
+        repr: None,
+        skip: false,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("stream_buf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("big_endian") {
+                field.endian = Some(Endian::Big);
+            } else if meta.path.is_ident("little_endian") {
+                field.endian = Some(Endian::Little);
+            } else if meta.path.is_ident("skip") {
+                field.skip = true;
+            } else if meta.path.is_ident("scale") {
+                field.scale = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("repr") {
+                field.repr = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown stream_buf field attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(field)
+}
+
+fn field_infos(fields: &Fields, default_endian: Option<Endian>) -> Result<Vec<FieldInfo>> {
+    let mut infos = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let attrs = parse_field_attrs(&field.attrs)?;
+        let scale = match (attrs.scale, attrs.repr) {
+            (Some(scale), Some(repr)) => {
+                primitive_size(&repr).ok_or_else(|| Error::new(repr.span(), "repr must be a primitive type"))?;
+                Some((scale, repr))
+            }
+            (None, None) => None,
+            _ => return Err(Error::new(field.span(), "`scale` and `repr` must be used together")),
+        };
+        // the container byte order only applies to primitive fields, other fields use their Encode and Decode impls
+        let endian = if primitive_size(&field.ty).is_some() || scale.is_some() {
+            attrs.endian.or(default_endian)
+        } else {
+            attrs.endian
+        };
+        let binding = match &field.ident {
+            Some(ident) => format_ident!("__field_{}", ident),
+            None => format_ident!("__field_{}", index),
+        };
+        infos.push(FieldInfo {
+            member: field.ident.clone(),
+            binding,
+            ty: field.ty.clone(),
+            endian,
+            scale,
+            skip: attrs.skip,
+        });
+    }
+    Ok(infos)
+}
+
+/// Return the size of `ty` if it is one of the primitive types supported by the stream_buf.
+fn primitive_size(ty: &Type) -> Option<usize> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let ident = type_path.path.get_ident()?;
+    PRIMITIVES.iter().find(|(name, _)| ident == name).map(|(_, size)| *size)
+}
+
+/// Return the name of the `try_read_*` or `try_write_*` function for a primitive type.
+fn primitive_function(ty: &Type, endian: Option<Endian>, prefix: &str) -> Result<Ident> {
+    let size = primitive_size(ty).ok_or_else(|| {
+        Error::new(
+            ty.span(),
+            "big_endian and little_endian are only supported on primitive fields",
+        )
+    })?;
+    let name = quote!(#ty).to_string();
+    match endian {
+        Some(Endian::Big) if size > 1 => Ok(format_ident!("{}_{}_big_endian", prefix, name)),
+        Some(Endian::Little) if size > 1 => Ok(format_ident!("{}_{}_little_endian", prefix, name)),
+        _ => Ok(format_ident!("{}_{}", prefix, name)),
+    }
+}
+
+fn encode_value(value: &TokenStream2, ty: &Type, endian: Option<Endian>) -> Result<TokenStream2> {
+    if endian.is_none() {
+        return Ok(quote! { ::stream_buf::Encode::encode(#value, writer)?; });
+    }
+    let function = primitive_function(ty, endian, "try_write")?;
+    Ok(quote! { writer.#function(*#value)?; })
+}
+
+fn decode_value(ty: &Type, endian: Option<Endian>) -> Result<TokenStream2> {
+    if endian.is_none() {
+        return Ok(quote! { <#ty as ::stream_buf::Decode>::decode(reader)? });
+    }
+    let function = primitive_function(ty, endian, "try_read")?;
+    Ok(quote! { reader.#function()? })
+}
+
+fn encode_field(field: &FieldInfo) -> Result<TokenStream2> {
+    let binding = &field.binding;
+    match &field.scale {
+        None => encode_value(&quote!(#binding), &field.ty, field.endian),
+        Some((scale, repr)) => {
+            let encode_raw = encode_value(&quote!(&raw), repr, field.endian)?;
+            Ok(quote! {
+                {
+                    let raw = ::stream_buf::round_scaled(*#binding as f64, (#scale) as f64) as #repr;
+                    #encode_raw
+                }
+            })
+        }
+    }
+}
+
+fn decode_field(field: &FieldInfo) -> Result<TokenStream2> {
+    if field.skip {
+        return Ok(quote! { ::core::default::Default::default() });
+    }
+    let ty = &field.ty;
+    match &field.scale {
+        None => decode_value(ty, field.endian),
+        Some((scale, repr)) => {
+            let decode_raw = decode_value(repr, field.endian)?;
+            Ok(quote! {
+                {
+                    let raw: #repr = #decode_raw;
+                    ((raw as f64) / ((#scale) as f64)) as #ty
+                }
+            })
+        }
+    }
+}
+
+/// Return the pattern that binds each field of `path` to its local variable.
+fn fields_pattern(path: TokenStream2, fields: &Fields, infos: &[FieldInfo]) -> TokenStream2 {
+    let bindings = infos.iter().map(|field| {
+        let binding = if field.skip {
+            quote!(_)
+        } else {
+            let binding = &field.binding;
+            quote!(#binding)
+        };
+        match &field.member {
+            Some(member) => quote!(#member: #binding),
+            None => binding,
+        }
+    });
+    match fields {
+        Fields::Named(_) => quote!(#path { #(#bindings),* }),
+        Fields::Unnamed(_) => quote!(#path ( #(#bindings),* )),
+        Fields::Unit => path,
+    }
+}
+
+/// Return the expression that constructs `path` from the decoded fields.
+fn fields_constructor(path: TokenStream2, fields: &Fields, infos: &[FieldInfo]) -> Result<TokenStream2> {
+    let mut values = Vec::new();
+    for field in infos {
+        let value = decode_field(field)?;
+        values.push(match &field.member {
+            Some(member) => quote!(#member: #value),
+            None => value,
+        });
+    }
+    Ok(match fields {
+        Fields::Named(_) => quote!(#path { #(#values),* }),
+        Fields::Unnamed(_) => quote!(#path ( #(#values),* )),
+        Fields::Unit => path,
+    })
+}
+
+fn encode_fields(infos: &[FieldInfo]) -> Result<TokenStream2> {
+    let mut statements = Vec::new();
+    for field in infos.iter().filter(|field| !field.skip) {
+        statements.push(encode_field(field)?);
+    }
+    Ok(quote!(#(#statements)*))
+}
+
+fn add_trait_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
+
+/// Return the discriminant type of an enum, and the discriminant value of each variant.
+fn enum_discriminants(data: &DataEnum, container: &ContainerAttrs) -> Result<(Type, Vec<Literal>)> {
+    let ty = container.discriminant.clone().unwrap_or_else(|| parse_quote!(u8));
+    let max: u64 = match quote!(#ty).to_string().as_str() {
+        "u8" => u8::MAX.into(),
+        "u16" => u16::MAX.into(),
+        "u32" => u32::MAX.into(),
+        _ => return Err(Error::new(ty.span(), "discriminant must be u8, u16 or u32")),
+    };
+    let mut values = Vec::new();
+    let mut next: u64 = 0;
+    for variant in &data.variants {
+        if let Some((_, expr)) = &variant.discriminant {
+            next = match expr {
+                Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => int.base10_parse()?,
+                _ => return Err(Error::new(expr.span(), "discriminant must be an integer literal")),
+            };
+        }
+        if next > max {
+            return Err(Error::new(
+                variant.span(),
+                "discriminant does not fit in the discriminant type",
+            ));
+        }
+        values.push(Literal::u64_unsuffixed(next));
+        next += 1;
+    }
+    Ok((ty, values))
+}
+
+fn expand_encode(input: &DeriveInput) -> Result<TokenStream2> {
+    let container = parse_container_attrs(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let infos = field_infos(&data.fields, container.endian)?;
+            let pattern = fields_pattern(quote!(Self), &data.fields, &infos);
+            let statements = encode_fields(&infos)?;
+            quote! {
+                let #pattern = self;
+                #statements
+                ::core::result::Result::Ok(())
+            }
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "cannot derive StreamEncode for an empty enum",
+                ));
+            }
+            let (ty, values) = enum_discriminants(data, &container)?;
+            let write_discriminant = primitive_function(&ty, container.endian, "try_write")?;
+            let mut arms = Vec::new();
+            for (variant, value) in data.variants.iter().zip(values) {
+                let ident = &variant.ident;
+                let infos = field_infos(&variant.fields, container.endian)?;
+                let pattern = fields_pattern(quote!(Self::#ident), &variant.fields, &infos);
+                let statements = encode_fields(&infos)?;
+                arms.push(quote! {
+                    #pattern => {
+                        writer.#write_discriminant(#value)?;
+                        #statements
+                    }
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+                ::core::result::Result::Ok(())
+            }
+        }
+        Data::Union(_) => return Err(Error::new(input.ident.span(), "cannot derive StreamEncode for a union")),
+    };
+
+    let ident = &input.ident;
+    let generics = add_trait_bounds(&input.generics, quote!(::stream_buf::Encode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::stream_buf::Encode for #ident #ty_generics #where_clause {
+            fn encode<__E: ::stream_buf::ByteOrder>(
+                &self,
+                writer: &mut ::stream_buf::StreamBufWriter<'_, __E>,
+            ) -> ::core::result::Result<(), ::stream_buf::WriteError> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_decode(input: &DeriveInput) -> Result<TokenStream2> {
+    let container = parse_container_attrs(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let infos = field_infos(&data.fields, container.endian)?;
+            let constructor = fields_constructor(quote!(Self), &data.fields, &infos)?;
+            quote! { ::core::result::Result::Ok(#constructor) }
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "cannot derive StreamDecode for an empty enum",
+                ));
+            }
+            let (ty, values) = enum_discriminants(data, &container)?;
+            let read_discriminant = primitive_function(&ty, container.endian, "try_read")?;
+            let mut arms = Vec::new();
+            for (variant, value) in data.variants.iter().zip(values) {
+                let ident = &variant.ident;
+                let infos = field_infos(&variant.fields, container.endian)?;
+                let constructor = fields_constructor(quote!(Self::#ident), &variant.fields, &infos)?;
+                arms.push(quote! { #value => ::core::result::Result::Ok(#constructor), });
+            }
+            quote! {
+                let pos = reader.pos();
+                match reader.#read_discriminant()? {
+                    #(#arms)*
+                    _ => ::core::result::Result::Err(::stream_buf::ReadError::Invalid { pos }),
+                }
+            }
+        }
+        Data::Union(_) => return Err(Error::new(input.ident.span(), "cannot derive StreamDecode for a union")),
+    };
+
+    let ident = &input.ident;
+    let generics = add_trait_bounds(&input.generics, quote!(::stream_buf::Decode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::stream_buf::Decode for #ident #ty_generics #where_clause {
+            fn decode<__E: ::stream_buf::ByteOrder>(
+                reader: &mut ::stream_buf::StreamBufReader<'_, __E>,
+            ) -> ::core::result::Result<Self, ::stream_buf::ReadError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,170 @@
+use stream_buf::{BigEndian, ReadError, StreamBufReader, StreamBufWriter, StreamDecode, StreamEncode};
+
+#[derive(Debug, Default, PartialEq, StreamEncode, StreamDecode)]
+struct Telemetry {
+    gyro: [i16; 3],
+    #[stream_buf(big_endian)]
+    timestamp_us: u64,
+    #[stream_buf(scale = 100.0, repr = i16)]
+    roll_degrees: f32,
+    #[stream_buf(skip)]
+    cached: u32,
+    armed: bool,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[stream_buf(big_endian)]
+struct Position(
+    [u8; 2],
+    i32,
+    #[stream_buf(little_endian)] i32,
+    #[stream_buf(scale = 1e7, repr = i32)] f64,
+);
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Scaled {
+    #[stream_buf(scale = 1.0, repr = i32)]
+    below_half: f64,
+    #[stream_buf(scale = 1.0, repr = i64)]
+    large: f64,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[stream_buf(discriminant = u16, big_endian)]
+#[repr(u16)]
+enum Command {
+    Reboot,
+    SetRate(u16),
+    SetPid { axis: u8, p: u16, i: u16 } = 10,
+    Arm,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Header {
+    #[stream_buf(little_endian)]
+    magic: u16,
+    length: u16,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Wrapper<T> {
+    inner: T,
+    flags: Option<u8>,
+}
+
+#[test]
+fn derive_struct() {
+    let telemetry = Telemetry {
+        gyro: [1, -2, 3],
+        timestamp_us: 0x0102_0304_0506_0708,
+        roll_degrees: -12.345,
+        cached: 99,
+        armed: true,
+    };
+    let mut data = [0u8; 32];
+    let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    assert_eq!(Ok(()), sbuf_writer.put(&telemetry));
+    assert_eq!(6 + 8 + 2 + 1, sbuf_writer.bytes_written());
+    assert_eq!(
+        [
+            0x01, 0x00, 0xfe, 0xff, 0x03, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x2d, 0xfb, 0x01
+        ],
+        sbuf_writer.get_ref()
+    );
+
+    let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    let decoded = sbuf_reader.get::<Telemetry>();
+    assert_eq!(
+        Ok(Telemetry {
+            roll_degrees: -12.35,
+            cached: 0,
+            ..telemetry
+        }),
+        decoded
+    );
+}
+
+#[test]
+fn derive_tuple_struct() {
+    let position = Position([1, 2], -1, -1, 51.4778394);
+    let mut data = [0u8; 14];
+    let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    assert_eq!(Ok(()), sbuf_writer.put(&position));
+    assert_eq!([0x01, 0x02, 0xff, 0xff, 0xff, 0xff], sbuf_writer.get_ref()[0..6]);
+    assert_eq!(514_778_394i32.to_be_bytes(), sbuf_writer.get_ref()[10..14]);
+
+    let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    assert_eq!(Ok(position), sbuf_reader.get::<Position>());
+}
+
+#[test]
+fn derive_scaled_rounding() {
+    // adding 0.5 before truncating would round these up
+    let scaled = Scaled {
+        below_half: 0.49999999999999994,
+        large: 4_503_599_627_370_497.0,
+    };
+    let mut data = [0u8; 12];
+    let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    assert_eq!(Ok(()), sbuf_writer.put(&scaled));
+    assert_eq!([0x00, 0x00, 0x00, 0x00], sbuf_writer.get_ref()[0..4]);
+    assert_eq!(4_503_599_627_370_497i64.to_le_bytes(), sbuf_writer.get_ref()[4..12]);
+}
+
+#[test]
+fn derive_enum() {
+    let mut data = [0u8; 32];
+    let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    assert_eq!(Ok(()), sbuf_writer.put(&Command::Reboot));
+    assert_eq!(Ok(()), sbuf_writer.put(&Command::SetRate(0x1234)));
+    assert_eq!(Ok(()), sbuf_writer.put(&Command::SetPid { axis: 2, p: 45, i: 80 }));
+    assert_eq!(Ok(()), sbuf_writer.put(&Command::Arm));
+    assert_eq!(
+        [
+            0x00, 0x00, 0x00, 0x01, 0x12, 0x34, 0x00, 0x0a, 0x02, 0x00, 0x2d, 0x00, 0x50, 0x00, 0x0b
+        ],
+        sbuf_writer.get_ref()
+    );
+    sbuf_writer.write_u16_big_endian(3);
+
+    let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    assert_eq!(Ok(Command::Reboot), sbuf_reader.get::<Command>());
+    assert_eq!(Ok(Command::SetRate(0x1234)), sbuf_reader.get::<Command>());
+    assert_eq!(
+        Ok(Command::SetPid { axis: 2, p: 45, i: 80 }),
+        sbuf_reader.get::<Command>()
+    );
+    assert_eq!(Ok(Command::Arm), sbuf_reader.get::<Command>());
+    assert_eq!(Err(ReadError::Invalid { pos: 15 }), sbuf_reader.get::<Command>());
+    assert_eq!(15, sbuf_reader.pos());
+}
+
+#[test]
+fn derive_generic() {
+    let wrapper = Wrapper {
+        inner: (1u8, -1i32),
+        flags: Some(7),
+    };
+    let mut data = [0u8; 8];
+    let mut sbuf_writer = StreamBufWriter::new(&mut data);
+    assert_eq!(Ok(()), sbuf_writer.put(&wrapper));
+    assert_eq!(7, sbuf_writer.bytes_written());
+
+    let mut sbuf_reader: StreamBufReader = sbuf_writer.into();
+    assert_eq!(Ok(wrapper), sbuf_reader.get::<Wrapper<(u8, i32)>>());
+}
+
+#[test]
+fn derive_follows_stream_buf_byte_order() {
+    let header = Header {
+        magic: 0x0a1b,
+        length: 0x2c3d,
+    };
+    let mut data = [0u8; 4];
+    let mut sbuf_writer = StreamBufWriter::with_byte_order(&mut data, BigEndian);
+    assert_eq!(Ok(()), sbuf_writer.put(&header));
+    assert_eq!([0x1b, 0x0a, 0x2c, 0x3d], sbuf_writer.get_ref());
+
+    let mut sbuf_reader: StreamBufReader<'_, BigEndian> = sbuf_writer.into();
+    assert_eq!(Ok(header), sbuf_reader.get::<Header>());
+}